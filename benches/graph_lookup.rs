@@ -0,0 +1,70 @@
+//! Compares `Graph::get_result`, a binary search over the arcs, with the hash map the graph used
+//! to keep next to its arcs.
+//!
+//! Run with `cargo bench --bench graph_lookup -- [num_queries]` next to `recipe-map.bincode`.
+
+use std::{collections::HashMap, error::Error, hint::black_box, mem::size_of, time::Instant};
+
+use infinite_craft::RecipeMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1).filter(|arg| arg != "--bench");
+    let num_queries = args.next().map_or(Ok(10_000_000), |arg| arg.parse())?;
+
+    let rm = RecipeMap::from_bytes(&std::fs::read("recipe-map.bincode")?)?;
+    let graph = rm.make_graph();
+    let map: HashMap<[u32; 2], u32> = rm
+        .items()
+        .flat_map(|u1| graph.arcs_from(u1).map(move |(u2, u3)| ([u1, u2], u3)))
+        .collect();
+    println!(
+        "{} items, {} recipes, {} queries",
+        rm.num_items(),
+        rm.num_recipes(),
+        num_queries
+    );
+
+    // half of the queries are recipes, the other half mostly untested pairs
+    let pairs: Vec<_> = map.keys().copied().collect();
+    let mut rng = StdRng::seed_from_u64(0);
+    let n = rm.num_items() as u32;
+    let queries: Vec<[u32; 2]> = (0..num_queries)
+        .map(|i| match i % 2 {
+            0 => pairs[rng.gen_range(0..pairs.len())],
+            _ => [rng.gen_range(0..n), rng.gen_range(0..n)],
+        })
+        .collect();
+
+    // the table of a hash map has a control byte per bucket
+    let map_size = map.capacity() * (size_of::<([u32; 2], u32)>() + 1);
+    let graph_size = graph.heap_size();
+
+    let instant = Instant::now();
+    let found = queries
+        .iter()
+        .filter(|&&[u1, u2]| black_box(graph.get_result(u1, u2)).is_some())
+        .count();
+    let graph_elapsed = instant.elapsed();
+
+    let instant = Instant::now();
+    let map_found = queries
+        .iter()
+        .filter(|&pair| black_box(map.get(pair)).is_some())
+        .count();
+    let map_elapsed = instant.elapsed();
+    assert_eq!(found, map_found);
+
+    for (name, size, elapsed) in [
+        ("arcs", graph_size, graph_elapsed),
+        ("arcs + map", graph_size + map_size, map_elapsed),
+    ] {
+        println!(
+            "{:>10}: {:>8} KiB, {:>6.1}ns/lookup",
+            name,
+            size >> 10,
+            elapsed.as_nanos() as f64 / num_queries as f64
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Compares the `ItemSet` representations of `UniformFamily` on the real recipe map.
+//!
+//! Run with `cargo bench --bench item_set -- [max_card] [max_count]` next to `recipe-map.bincode`.
+
+use std::{error::Error, time::Instant};
+
+use infinite_craft::{bitset::BitSet, search::Search, uniform_family::ItemSet, Graph, RecipeMap};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1).filter(|arg| arg != "--bench");
+    let max_card = args.next().map_or(Ok(300), |arg| arg.parse())?;
+    let max_count = args.next().map_or(Ok(usize::MAX), |arg| arg.parse())?;
+
+    let rm = RecipeMap::from_bytes(&std::fs::read("recipe-map.bincode")?)?;
+    let graph = rm.make_graph();
+    let source = ["Water", "Fire", "Wind", "Earth"].map(|u| rm.id(u));
+    println!(
+        "{} items, {} recipes, max_card={}, max_count={}",
+        rm.num_items(),
+        rm.num_recipes(),
+        max_card,
+        max_count
+    );
+
+    let vec = bench::<Vec<u32>>("Vec<u32>", &source, &graph, &rm, max_count, max_card);
+    let bitset = bench::<BitSet>("BitSet", &source, &graph, &rm, max_count, max_card);
+    for u in rm.items() {
+        assert_eq!(
+            vec.sets(u).iter().cloned().collect::<Vec<_>>(),
+            bitset
+                .sets(u)
+                .iter()
+                .map(|set| set.to_vec())
+                .collect::<Vec<_>>(),
+            "{:?}",
+            rm.name(u)
+        );
+    }
+    Ok(())
+}
+
+fn bench<S: ItemSet>(
+    name: &str,
+    source: &[u32],
+    graph: &Graph,
+    rm: &RecipeMap,
+    max_count: usize,
+    max_card: usize,
+) -> Search<S> {
+    let instant = Instant::now();
+    let mut search = Search::<S>::with_item_set(rm.num_items(), max_count, max_card);
+    search.search_from_source(source, graph);
+    let elapsed = instant.elapsed();
+
+    let (num_sets, heap_size) = rm
+        .items()
+        .flat_map(|u| search.sets(u).iter())
+        .fold((0, 0), |(n, size), set| (n + 1, size + set.heap_size()));
+    println!(
+        "{:>10}: {:>8}ms, {} sets, {} KiB",
+        name,
+        elapsed.as_millis(),
+        num_sets,
+        heap_size >> 10
+    );
+    search
+}
@@ -0,0 +1,37 @@
+//! Compares the time to open a recipe map file in place, with and without verifying it, and to
+//! read it with `from_bytes`.
+//!
+//! Run with `cargo bench --bench open_map -- [path]` next to `recipe-map.bincode`. The first run
+//! after the file was written may include reading it from disk.
+
+use std::{error::Error, hint::black_box, path::PathBuf, time::Instant};
+
+use infinite_craft::RecipeMap;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = std::env::args().skip(1).filter(|arg| arg != "--bench");
+    let path = PathBuf::from(args.next().unwrap_or("recipe-map.bincode".to_string()));
+
+    let instant = Instant::now();
+    let rm = RecipeMap::open(&path)?;
+    let open_elapsed = instant.elapsed();
+    println!(
+        "{} items, {} recipes, {} bytes",
+        rm.num_items(),
+        rm.num_recipes(),
+        std::fs::metadata(&path)?.len()
+    );
+    println!("open: {:.3}ms", open_elapsed.as_secs_f64() * 1e3);
+
+    let instant = Instant::now();
+    black_box(RecipeMap::open_verified(&path)?);
+    println!(
+        "open_verified: {:.3}ms",
+        instant.elapsed().as_secs_f64() * 1e3
+    );
+
+    let instant = Instant::now();
+    black_box(RecipeMap::from_bytes(&std::fs::read(&path)?)?);
+    println!("from_bytes: {:.3}ms", instant.elapsed().as_secs_f64() * 1e3);
+    Ok(())
+}
@@ -0,0 +1,322 @@
+//! Readers of the recipe sources.
+
+use std::{borrow::Cow, collections::HashMap, fmt, path::Path, str::FromStr};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use infinite_craft::{ItemInfo, Provenance, RecipeMap};
+use rusqlite::{types::ValueRef, Connection, OpenFlags};
+
+/// Reader of one format of recipe source.
+pub trait Importer {
+    /// Inserts the recipes in `path` into `rm` as learned from `source`.
+    fn import(&self, path: &Path, source: u32, rm: &mut RecipeMap) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// SQLite database with a `pair` table of the game responses, as `infinite-craft.db`
+    PairDb,
+    /// In-Finite-Craft `relevant_recipes.json` mapping each result to its pairs
+    RelevantJson,
+    /// InfiniteCraftHelper SQLite database with a `recipes` table, as `helper-recipes.db`
+    HelperDb,
+}
+
+impl Format {
+    pub fn importer(self) -> Box<dyn Importer> {
+        match self {
+            Self::PairDb => Box::new(PairDb),
+            Self::RelevantJson => Box::new(RelevantJson),
+            Self::HelperDb => Box::new(HelperDb),
+        }
+    }
+}
+
+/// A source to read, given on the command line as `FORMAT[@PRIORITY]=PATH`.
+#[derive(Debug, Clone)]
+pub struct Input {
+    pub format: Format,
+    /// Smaller is preferred. Inputs without one come after those with one.
+    pub priority: Option<u32>,
+    pub path: String,
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (spec, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected FORMAT[@PRIORITY]=PATH, got {:?}", s))?;
+        let (format, priority) = match spec.split_once('@') {
+            Some((format, priority)) => {
+                let priority = priority
+                    .parse()
+                    .map_err(|e| format!("invalid priority {:?}: {}", priority, e))?;
+                (format, Some(priority))
+            }
+            None => (spec, None),
+        };
+        if path.is_empty() {
+            return Err(format!("missing path in {:?}", s));
+        }
+        Ok(Self {
+            format: Format::from_str(format, true)?,
+            priority,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = self.format.to_possible_value().unwrap();
+        write!(f, "{}", format.get_name())?;
+        if let Some(priority) = self.priority {
+            write!(f, "@{}", priority)?;
+        }
+        write!(f, "={}", self.path)
+    }
+}
+
+pub struct PairDb;
+
+impl Importer for PairDb {
+    fn import(&self, path: &Path, source: u32, rm: &mut RecipeMap) -> anyhow::Result<()> {
+        let conn = open_db(path)?;
+        read_pair_all(&conn, source, rm)?;
+        read_item_info(&conn, "items", source, rm)
+    }
+}
+
+pub struct RelevantJson;
+
+impl Importer for RelevantJson {
+    fn import(&self, path: &Path, source: u32, rm: &mut RecipeMap) -> anyhow::Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        read_relevant_recipes(&text, source, rm)
+    }
+}
+
+pub struct HelperDb;
+
+impl Importer for HelperDb {
+    fn import(&self, path: &Path, source: u32, rm: &mut RecipeMap) -> anyhow::Result<()> {
+        let conn = open_db(path)?;
+        read_helper_recipes(&conn, source, rm)?;
+        read_item_info(&conn, "items", source, rm)
+    }
+}
+
+fn open_db(path: &Path) -> anyhow::Result<Connection> {
+    // opening a missing file read-only fails only at the first query otherwise
+    std::fs::metadata(path)?;
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .context("cannot open the database")
+}
+
+pub fn read_pair_all(conn: &Connection, source: u32, rm: &mut RecipeMap) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("select first, second, result from pair")?;
+    let mut iter = stmt.query(())?;
+    while let Some(row) = iter.next()? {
+        let first = row.get_ref(0)?.as_str()?;
+        let second = row.get_ref(1)?.as_str()?;
+        let provenance = Provenance::new(source, None);
+        match row.get_ref(2)?.as_str_or_null()? {
+            Some(result) => rm.insert_from(first, second, result, provenance),
+            None => rm.insert_nothing_from(first, second, provenance),
+        };
+    }
+    Ok(())
+}
+
+pub fn read_relevant_recipes(text: &str, source: u32, rm: &mut RecipeMap) -> anyhow::Result<()> {
+    let data: HashMap<Cow<'_, str>, Vec<[Cow<'_, str>; 2]>> = serde_json::from_str(text)?;
+    let mut data = data.into_iter().collect::<Vec<_>>();
+    data.sort_by(|x, y| x.0.cmp(&y.0));
+    for (result, pairs) in data {
+        for [first, second] in pairs {
+            let provenance = Provenance::new(source, None);
+            rm.insert_from(first.as_ref(), second.as_ref(), result.as_ref(), provenance);
+        }
+    }
+    Ok(())
+}
+
+pub fn read_helper_recipes(
+    conn: &Connection,
+    source: u32,
+    rm: &mut RecipeMap,
+) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("select first, second, result from recipes")?;
+    let mut iter = stmt.query(())?;
+    while let Some(row) = iter.next()? {
+        let first = row.get_ref(0)?.as_str()?;
+        let second = row.get_ref(1)?.as_str()?;
+        let result = row.get_ref(2)?.as_str()?;
+        let provenance = Provenance::new(source, None);
+        if result == "Nothing" {
+            rm.insert_nothing_from(first, second, provenance);
+            continue;
+        }
+        rm.insert_from(first, second, result, provenance);
+    }
+    Ok(())
+}
+
+/// Reads the item metadata from the columns `table` has, if it exists. The schemas of the
+/// databases vary, so the columns are found by the names they are known to have: `name`, `text`
+/// or `item` for the name, `emoji`, `first_discovery`, `discovered` or `is_new` for first
+/// discoveries and `first_seen`, `timestamp` or `created_at` for the time. Values of unexpected
+/// types are read as missing instead of failing the import.
+pub fn read_item_info(
+    conn: &Connection,
+    table: &str,
+    source: u32,
+    rm: &mut RecipeMap,
+) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare("select name from pragma_table_info(?)")?;
+    let columns = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let find = |names: &[&'static str]| {
+        names
+            .iter()
+            .copied()
+            .find(|&name| columns.iter().any(|c| c.eq_ignore_ascii_case(name)))
+    };
+    let Some(name) = find(&["name", "text", "item"]) else {
+        return Ok(());
+    };
+    let [emoji, first_discovery, first_seen] = [
+        find(&["emoji"]),
+        find(&["first_discovery", "discovered", "is_new"]),
+        find(&["first_seen", "timestamp", "created_at"]),
+    ]
+    .map(|column| column.unwrap_or("null"));
+
+    let mut stmt = conn.prepare(&format!(
+        "select {}, {}, {}, {} from {}",
+        name, emoji, first_discovery, first_seen, table
+    ))?;
+    let mut iter = stmt.query(())?;
+    while let Some(row) = iter.next()? {
+        let name = row.get_ref(0)?.as_str()?;
+        let info = ItemInfo {
+            emoji: row
+                .get_ref(1)?
+                .as_str_or_null()?
+                .filter(|emoji| !emoji.is_empty())
+                .map(str::to_owned),
+            first_discovery: lenient_bool(row.get_ref(2)?),
+            first_seen: lenient_timestamp(row.get_ref(3)?),
+            source: Some(source),
+        };
+        rm.insert_item_info(name, info);
+    }
+    Ok(())
+}
+
+fn lenient_bool(value: ValueRef) -> bool {
+    match value {
+        ValueRef::Integer(i) => i != 0,
+        ValueRef::Real(f) => f != 0.0,
+        ValueRef::Text(text) => {
+            let text = String::from_utf8_lossy(text);
+            ["1", "true", "yes"].contains(&text.trim().to_ascii_lowercase().as_str())
+        }
+        ValueRef::Null | ValueRef::Blob(_) => false,
+    }
+}
+
+/// Seconds since the Unix epoch, from an integer or a number in text.
+fn lenient_timestamp(value: ValueRef) -> Option<u64> {
+    match value {
+        ValueRef::Integer(i) => u64::try_from(i).ok(),
+        ValueRef::Real(f) if f >= 0.0 => Some(f as u64),
+        ValueRef::Text(text) => std::str::from_utf8(text).ok()?.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_inputs() {
+        let input: Input = "helper-db@2=recipes.db".parse().unwrap();
+        assert_eq!(input.format, Format::HelperDb);
+        assert_eq!(input.priority, Some(2));
+        assert_eq!(input.path, "recipes.db");
+        assert_eq!(input.to_string(), "helper-db@2=recipes.db");
+
+        let input: Input = "Relevant-Json=data/a=b.json".parse().unwrap();
+        assert_eq!(input.format, Format::RelevantJson);
+        assert_eq!(input.priority, None);
+        assert_eq!(input.path, "data/a=b.json");
+        assert_eq!(input.to_string(), "relevant-json=data/a=b.json");
+
+        for malformed in [
+            "recipes.db",
+            "sqlite=recipes.db",
+            "pair-db@=x.db",
+            "pair-db@first=x.db",
+            "pair-db@-1=x.db",
+            "pair-db=",
+        ] {
+            assert!(malformed.parse::<Input>().is_err(), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn nothing_results_record_the_source() -> anyhow::Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "create table pair (first, second, result);
+             insert into pair values ('Water', 'Fire', 'Steam');
+             insert into pair values ('Water', 'Water', null);
+             create table recipes (first, second, result);
+             insert into recipes values ('Fire', 'Fire', 'Nothing');",
+        )?;
+        let mut rm = RecipeMap::new();
+        let [pair_db, helper_db] = ["pair.db", "helper.db"].map(|s| rm.add_source(s));
+        read_pair_all(&conn, pair_db, &mut rm)?;
+        read_helper_recipes(&conn, helper_db, &mut rm)?;
+
+        let [water, fire] = ["Water", "Fire"].map(|u| rm.id(u));
+        assert!(rm.is_nothing(water, water) && rm.is_nothing(fire, fire));
+        let source = |u1, u2| rm.provenance(u1, u2).map(|p| p.source);
+        assert_eq!(source(water, fire), Some(pair_db));
+        assert_eq!(source(water, water), Some(pair_db));
+        assert_eq!(source(fire, fire), Some(helper_db));
+        Ok(())
+    }
+
+    #[test]
+    fn item_info_is_read_leniently() -> anyhow::Result<()> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch(
+            "create table items (text, emoji, discovered, timestamp);
+             insert into items values ('Steam', '💨', 'true', 10);
+             insert into items values ('Lava', '', 'no', '20');
+             insert into items values ('Mud', null, 1.0, 'yesterday');",
+        )?;
+        let mut rm = RecipeMap::new();
+        let source = rm.add_source("items.db");
+        read_item_info(&conn, "items", source, &mut rm)?;
+
+        let info = |name| rm.item_info(rm.id(name)).unwrap();
+        assert_eq!(info("Steam").emoji.as_deref(), Some("💨"));
+        assert!(info("Steam").first_discovery);
+        assert_eq!(info("Steam").first_seen, Some(10));
+        assert_eq!(info("Lava").emoji, None);
+        assert!(!info("Lava").first_discovery);
+        assert_eq!(info("Lava").first_seen, Some(20));
+        assert!(info("Mud").first_discovery);
+        assert_eq!(info("Mud").first_seen, None);
+        assert_eq!(info("Mud").source, None);
+        Ok(())
+    }
+}
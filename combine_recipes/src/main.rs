@@ -1,76 +1,179 @@
-use std::{borrow::Cow, collections::HashMap};
+mod import;
 
-use infinite_craft::RecipeMap;
-use rusqlite::{Connection, OpenFlags};
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use import::{Format, Input};
+use infinite_craft::{
+    conflict::{Conflict, ConflictPolicy},
+    names::Normalization,
+    RecipeMap,
+};
+use serde::Serialize;
+
+/// Combine the recipe sources into `recipe-map.bincode`
+#[derive(Parser)]
+struct Cli {
+    /// Source to read as FORMAT[@PRIORITY]=PATH, where FORMAT is pair-db, relevant-json or
+    /// helper-db. Sources are read by increasing priority, then in the given order; without any,
+    /// the files of each format are read from the current directory if they exist
+    #[arg(long = "input", value_name = "FORMAT[@PRIORITY]=PATH")]
+    inputs: Vec<Input>,
+
+    /// How to choose the result of a pair the sources disagree on
+    #[arg(long, value_enum, default_value_t = Policy::FirstWins)]
+    policy: Policy,
+
+    /// File to write the disagreeing recipes to as JSON
+    #[arg(long, default_value = "recipe-conflicts.json")]
+    conflicts: PathBuf,
+
+    /// Normalizations applied to the item names, comma separated
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "nfc,trim")]
+    normalize: Vec<Normalize>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Policy {
+    /// Keep the result read first
+    FirstWins,
+    /// Prefer the source whose input has the smaller priority number, regardless of the order
+    /// of the recipes within the sources
+    Priority,
+    /// Take the result given most often, dropping ties
+    Majority,
+    /// Drop every pair with disagreeing results
+    Reject,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Normalize {
+    /// Unicode normalization form C
+    Nfc,
+    /// Strip leading and trailing whitespace
+    Trim,
+    /// Keep the names as they are
+    None,
+}
 
 pub fn main() -> anyhow::Result<()> {
-    let mut rm = RecipeMap::new();
+    let cli = Cli::parse();
+    let explicit = !cli.inputs.is_empty();
+    let mut inputs = if explicit {
+        cli.inputs
+    } else {
+        [
+            (Format::PairDb, "infinite-craft.db"),
+            (Format::RelevantJson, "relevant_recipes.json"),
+            (Format::HelperDb, "helper-recipes.db"),
+        ]
+        .map(|(format, path)| Input {
+            format,
+            priority: None,
+            path: path.to_string(),
+        })
+        .to_vec()
+    };
+    inputs.sort_by_key(|input| input.priority.unwrap_or(u32::MAX));
 
-    let path = "infinite-craft.db";
-    if let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
-        println!("Reading {}", path);
-        read_pair_all(&conn, &mut rm)?;
-    }
+    let mut rm = RecipeMap::new();
+    rm.set_conflict_policy(match cli.policy {
+        Policy::FirstWins => ConflictPolicy::FirstWins,
+        // sources are numbered in the reading order, which is by priority
+        Policy::Priority => ConflictPolicy::SourcePriority((0..inputs.len() as u32).collect()),
+        Policy::Majority => ConflictPolicy::MajorityVote,
+        Policy::Reject => ConflictPolicy::RejectBoth,
+    });
+    rm.set_normalization(Normalization {
+        nfc: cli.normalize.contains(&Normalize::Nfc),
+        trim: cli.normalize.contains(&Normalize::Trim),
+    });
 
-    let path = "relevant_recipes.json";
-    if let Ok(text) = std::fs::read_to_string(path) {
-        println!("Reading {}", path);
-        read_relevant_recipes(&text, &mut rm)?;
+    for input in &inputs {
+        let path = Path::new(&input.path);
+        match std::fs::metadata(path) {
+            Err(e) if e.kind() == ErrorKind::NotFound && !explicit => {
+                println!("Skipping {}: not found", input.path);
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("cannot read input {}", input)),
+            Ok(_) => {}
+        }
+        println!("Reading {}", input);
+        let source = rm.add_source(&input.path);
+        input
+            .format
+            .importer()
+            .import(path, source, &mut rm)
+            .with_context(|| format!("cannot import {}", input))?;
     }
 
-    let path = "helper-recipes.db";
-    if let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
-        println!("Reading {}", path);
-        read_helper_recipes(&conn, &mut rm)?;
-    }
+    println!(
+        "{} items, {} recipes, {} pairs combining into nothing",
+        rm.num_items(),
+        rm.num_recipes(),
+        rm.num_nothing()
+    );
 
-    println!("{} items, {} recipes", rm.num_items(), rm.num_recipes());
+    let conflicts = rm.conflicts();
+    let report: Vec<_> = conflicts
+        .iter()
+        .map(|c| ConflictEntry::new(c, &rm))
+        .collect();
+    std::fs::write(&cli.conflicts, serde_json::to_string_pretty(&report)?)?;
+    println!(
+        "{} conflicts, {} unresolved, written {}",
+        conflicts.len(),
+        conflicts.iter().filter(|c| c.result.is_none()).count(),
+        cli.conflicts.display()
+    );
 
     let path = "recipe-map.bincode";
-    std::fs::write(path, &bincode::serialize(&rm.as_serializable())?)?;
+    // written through a temporary file and renamed, so that a solver with the old file mapped
+    // keeps reading the old contents
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, rm.to_bytes())?;
+    std::fs::rename(&tmp, path).with_context(|| format!("cannot write {}", path))?;
     println!("Written {}", path);
 
     Ok(())
 }
 
-pub fn read_pair_all(conn: &Connection, rm: &mut RecipeMap) -> anyhow::Result<()> {
-    let mut stmt = conn.prepare("select first, second, result from pair")?;
-    let mut iter = stmt.query(())?;
-    while let Some(row) = iter.next()? {
-        let first = row.get_ref(0)?.as_str()?;
-        let second = row.get_ref(1)?.as_str()?;
-        if let Some(result) = row.get_ref(2)?.as_str_or_null()? {
-            rm.insert(first, second, result);
-        }
-    }
-    Ok(())
+#[derive(Serialize)]
+struct ConflictEntry<'a> {
+    first: &'a str,
+    second: &'a str,
+    claims: Vec<ClaimEntry<'a>>,
+    result: Option<&'a str>,
 }
 
-pub fn read_relevant_recipes(text: &str, rm: &mut RecipeMap) -> anyhow::Result<()> {
-    let data: HashMap<Cow<'_, str>, Vec<[Cow<'_, str>; 2]>> = serde_json::from_str(text)?;
-    let mut data = data.into_iter().collect::<Vec<_>>();
-    data.sort_by(|x, y| x.0.cmp(&y.0));
-    for (result, pairs) in data {
-        for [first, second] in pairs {
-            if first.is_ascii() && second.is_ascii() && result.is_ascii() {
-                rm.insert(first.as_ref(), second.as_ref(), result.as_ref());
-            }
-        }
-    }
-    Ok(())
+#[derive(Serialize)]
+struct ClaimEntry<'a> {
+    result: &'a str,
+    source: Option<&'a str>,
+    timestamp: Option<u64>,
 }
 
-pub fn read_helper_recipes(conn: &Connection, rm: &mut RecipeMap) -> anyhow::Result<()> {
-    let mut stmt = conn.prepare("select first, second, result from recipes")?;
-    let mut iter = stmt.query(())?;
-    while let Some(row) = iter.next()? {
-        let first = row.get_ref(0)?.as_str()?;
-        let second = row.get_ref(1)?.as_str()?;
-        let result = row.get_ref(2)?.as_str()?;
-        if result == "Nothing" {
-            continue;
+impl<'a> ConflictEntry<'a> {
+    fn new(conflict: &Conflict<'a>, rm: &'a RecipeMap) -> Self {
+        let [first, second] = conflict.pair;
+        Self {
+            first: rm.name(first),
+            second: rm.name(second),
+            claims: conflict
+                .claims
+                .iter()
+                .map(|claim| ClaimEntry {
+                    result: &claim.result,
+                    source: claim.provenance.map(|p| rm.source_name(p.source)),
+                    timestamp: claim.provenance.and_then(|p| p.timestamp),
+                })
+                .collect(),
+            result: conflict.result.map(|u| rm.name(u)),
         }
-        rm.insert(first, second, result);
     }
-    Ok(())
 }
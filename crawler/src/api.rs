@@ -0,0 +1,107 @@
+//! Client of the pair combination endpoint.
+
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// Result the game gives for pairs that do not combine.
+pub const NOTHING: &str = "Nothing";
+
+/// Response of `pair?first=..&second=..`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairResponse {
+    pub result: String,
+    #[serde(default)]
+    pub emoji: String,
+    /// Whether the request made a first discovery.
+    #[serde(default)]
+    pub is_new: bool,
+}
+
+/// Makes requests no more often than the rate limit, retrying the failures worth retrying with
+/// exponential backoff.
+pub struct Client {
+    agent: ureq::Agent,
+    url: String,
+    min_interval: Duration,
+    retries: u32,
+    backoff: Duration,
+    last_request: Option<Instant>,
+}
+
+impl Client {
+    pub fn new(url: &str, min_interval: Duration, retries: u32, backoff: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build(),
+            url: url.to_string(),
+            min_interval,
+            retries,
+            backoff,
+            last_request: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn pair(&mut self, first: &str, second: &str) -> anyhow::Result<PairResponse> {
+        let mut attempt = 0;
+        loop {
+            self.wait();
+            let response = self
+                .agent
+                .get(&self.url)
+                .set("Referer", "https://neal.fun/infinite-craft/")
+                .query("first", first)
+                .query("second", second)
+                .call();
+            let (error, retry_after) = match response {
+                Ok(response) => {
+                    return response.into_json().with_context(|| {
+                        format!("invalid response for {:?} + {:?}", first, second)
+                    })
+                }
+                Err(ureq::Error::Status(status, response)) if status == 429 || status >= 500 => {
+                    let retry_after = response
+                        .header("Retry-After")
+                        .and_then(|secs| secs.parse().ok())
+                        .map(Duration::from_secs);
+                    (format!("status {}", status), retry_after)
+                }
+                Err(ureq::Error::Status(status, _)) => {
+                    bail!("status {} for {:?} + {:?}", status, first, second)
+                }
+                Err(ureq::Error::Transport(e)) => (e.to_string(), None),
+            };
+            if attempt == self.retries {
+                bail!(
+                    "{} for {:?} + {:?} after {} retries",
+                    error,
+                    first,
+                    second,
+                    attempt
+                );
+            }
+            let delay = retry_after
+                .unwrap_or_else(|| self.backoff.saturating_mul(2u32.pow(attempt.min(16))));
+            eprintln!("{}, retrying in {:?}", error, delay);
+            sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn wait(&mut self) {
+        if let Some(last) = self.last_request {
+            sleep(self.min_interval.saturating_sub(last.elapsed()));
+        }
+        self.last_request = Some(Instant::now());
+    }
+}
@@ -0,0 +1,162 @@
+//! Discovery of recipes by trying the untested pairs of the reachable items.
+
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use infinite_craft::{ItemInfo, Provenance, RecipeMap};
+
+use crate::api::{Client, NOTHING};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub requests: usize,
+    pub recipes: usize,
+    pub nothing: usize,
+    pub new_items: usize,
+}
+
+/// Tries the untested pairs of the items reachable from the source items, in the order the
+/// items are reached or the order of a queue, recording every answer in the recipe map as learned
+/// from the client URL.
+pub struct Crawler {
+    /// Reachable items in the order they were reached.
+    items: Vec<u32>,
+    reachable: Vec<bool>,
+    /// Next pair to try is `items[j]` and `items[i]`.
+    i: usize,
+    j: usize,
+    /// Pairs to try instead of every pair of reachable items.
+    queue: Option<VecDeque<[String; 2]>>,
+    source: u32,
+    pub stats: Stats,
+}
+
+impl Crawler {
+    /// Starts from the source items and the items the known recipes make from them.
+    pub fn new(rm: &mut RecipeMap, source_items: &[&str], url: &str) -> Self {
+        let source = rm.add_source(url);
+        let mut crawler = Self {
+            items: Vec::new(),
+            reachable: Vec::new(),
+            i: 0,
+            j: 0,
+            queue: None,
+            source,
+            stats: Stats::default(),
+        };
+        for name in source_items {
+            let u = rm.add_item(name);
+            crawler.reach(u, rm);
+        }
+        crawler
+    }
+
+    /// Tries only the given pairs, in order, skipping those already tested or of unknown items.
+    pub fn set_queue(&mut self, queue: Vec<[String; 2]>) {
+        self.queue = Some(queue.into());
+    }
+
+    /// Marks the item and everything the known recipes make from it and the other reachable
+    /// items.
+    fn reach(&mut self, u: u32, rm: &RecipeMap) {
+        self.reachable.resize(rm.num_items(), false);
+        if self.reachable[u as usize] {
+            return;
+        }
+        self.reachable[u as usize] = true;
+        let mut k = self.items.len();
+        self.items.push(u);
+        while let Some(&u1) = self.items.get(k) {
+            for l in 0..=k {
+                let Some(u3) = rm.get(u1, self.items[l]) else {
+                    continue;
+                };
+                if !self.reachable[u3 as usize] {
+                    self.reachable[u3 as usize] = true;
+                    self.items.push(u3);
+                }
+            }
+            k += 1;
+        }
+    }
+
+    /// Next untested pair of reachable items, if any. The pair stays next until tested.
+    fn next_pair(&mut self, rm: &RecipeMap) -> Option<[u32; 2]> {
+        if let Some(queue) = &mut self.queue {
+            while let Some([first, second]) = queue.front() {
+                if let (Some(u1), Some(u2)) = (rm.get_id(first), rm.get_id(second)) {
+                    if rm.is_untested(u1, u2) {
+                        return Some([u1, u2]);
+                    }
+                }
+                queue.pop_front();
+            }
+            return None;
+        }
+        while self.i < self.items.len() {
+            let pair = [self.items[self.j], self.items[self.i]];
+            if rm.is_untested(pair[0], pair[1]) {
+                return Some(pair);
+            }
+            if self.j == self.i {
+                self.i += 1;
+                self.j = 0;
+            } else {
+                self.j += 1;
+            }
+        }
+        None
+    }
+
+    /// Makes up to `max_requests` requests. Returns false when every pair to try is tested.
+    pub fn run(
+        &mut self,
+        rm: &mut RecipeMap,
+        client: &mut Client,
+        max_requests: usize,
+    ) -> anyhow::Result<bool> {
+        for _ in 0..max_requests {
+            let Some([first, second]) = self.next_pair(rm) else {
+                return Ok(false);
+            };
+            let names = [rm.name(first).to_string(), rm.name(second).to_string()];
+            let response = client.pair(&names[0], &names[1])?;
+            self.stats.requests += 1;
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|t| t.as_secs());
+
+            let provenance = Provenance::new(self.source, timestamp);
+            if response.result == NOTHING {
+                rm.insert_nothing_from(&names[0], &names[1], provenance);
+                self.stats.nothing += 1;
+                continue;
+            }
+            rm.insert_from(&names[0], &names[1], &response.result, provenance);
+            rm.insert_item_info(
+                &response.result,
+                ItemInfo {
+                    emoji: Some(response.emoji).filter(|emoji| !emoji.is_empty()),
+                    first_discovery: response.is_new,
+                    first_seen: timestamp,
+                    source: Some(self.source),
+                },
+            );
+            self.stats.recipes += 1;
+            let result = rm.add_item(&response.result);
+            if !self
+                .reachable
+                .get(result as usize)
+                .copied()
+                .unwrap_or(false)
+            {
+                self.stats.new_items += 1;
+                self.reach(result, rm);
+            }
+        }
+        Ok(self.next_pair(rm).is_some())
+    }
+}
@@ -0,0 +1,311 @@
+mod api;
+mod crawl;
+mod server;
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use api::Client;
+use clap::{Parser, Subcommand};
+use crawl::Crawler;
+use infinite_craft::RecipeMap;
+use serde::Deserialize;
+
+/// Discover recipes from a pair combination endpoint
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Try the untested pairs of the items reachable from the source items and add the results
+    /// to a recipe map
+    Crawl {
+        /// URL of the endpoint taking `first` and `second` query parameters
+        #[arg(long, default_value = "https://neal.fun/api/infinite-craft/pair")]
+        url: String,
+
+        /// Recipe map to extend, created if it does not exist
+        #[arg(long, default_value = "recipe-map.bincode")]
+        recipe_map: PathBuf,
+
+        /// Source item names
+        #[arg(long, value_delimiter = ',', default_value = "Water,Fire,Wind,Earth")]
+        source: Vec<String>,
+
+        /// Stop after this many requests
+        #[arg(long, default_value_t = 1000)]
+        max_requests: usize,
+
+        /// Maximum requests per second
+        #[arg(long, default_value_t = 2.0, value_parser = parse_rate)]
+        rate: f64,
+
+        /// Times to retry a request failing with a transport error, status 429 or a server error
+        #[arg(long, default_value_t = 5)]
+        retries: u32,
+
+        /// Seconds to wait before the first retry, doubled for each further one
+        #[arg(long, default_value_t = 1.0, value_parser = parse_backoff)]
+        backoff: f64,
+
+        /// Write the recipe map after every this many requests
+        #[arg(long, default_value_t = 100)]
+        save_interval: usize,
+
+        /// Try only the pairs in this file of JSON lines with `first` and `second` item names,
+        /// in order, as written by `infinite-craft plan --output`
+        #[arg(long)]
+        queue: Option<PathBuf>,
+    },
+    /// Answer pair requests from a recipe map, standing in for the game server
+    Serve {
+        /// Recipe map to answer from
+        #[arg(long, default_value = "recipe-map.bincode")]
+        recipe_map: PathBuf,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+
+        /// Fail every this many requests with status 503
+        #[arg(long)]
+        error_every: Option<usize>,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Crawl {
+            url,
+            recipe_map,
+            source,
+            max_requests,
+            rate,
+            retries,
+            backoff,
+            save_interval,
+            queue,
+        } => {
+            // read rather than mapped, since the file is replaced while crawling
+            let mut rm = match std::fs::read(&recipe_map) {
+                Ok(data) => RecipeMap::from_bytes(&data)
+                    .with_context(|| format!("cannot load {}", recipe_map.display()))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => RecipeMap::new(),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("cannot read {}", recipe_map.display()))
+                }
+            };
+            let min_interval = Duration::from_secs_f64(1.0 / rate);
+            let backoff = Duration::from_secs_f64(backoff);
+            let mut client = Client::new(&url, min_interval, retries, backoff);
+            let source: Vec<_> = source.iter().map(|s| s.as_str()).collect();
+            let mut crawler = Crawler::new(&mut rm, &source, client.url());
+            if let Some(path) = queue {
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("cannot read {}", path.display()))?;
+                let pairs = text
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| {
+                        let pair: QueuedPair = serde_json::from_str(line)?;
+                        Ok([pair.first, pair.second])
+                    })
+                    .collect::<anyhow::Result<_>>()
+                    .with_context(|| format!("invalid queue {}", path.display()))?;
+                crawler.set_queue(pairs);
+            }
+
+            let mut remaining = max_requests;
+            while remaining > 0 {
+                let batch = remaining.min(save_interval.max(1));
+                let result = crawler.run(&mut rm, &mut client, batch);
+                save(&rm, &recipe_map)?;
+                let stats = crawler.stats;
+                println!(
+                    "{} requests: {} recipes, {} nothing, {} new items",
+                    stats.requests, stats.recipes, stats.nothing, stats.new_items
+                );
+                if !result? {
+                    println!("Every pair of the reachable items is tested");
+                    break;
+                }
+                remaining -= batch;
+            }
+        }
+        Command::Serve {
+            recipe_map,
+            addr,
+            error_every,
+        } => {
+            let rm = RecipeMap::open(&recipe_map)
+                .with_context(|| format!("cannot load {}", recipe_map.display()))?;
+            let server = tiny_http::Server::http(&addr)
+                .map_err(|e| anyhow::anyhow!("cannot listen on {}: {}", addr, e))?;
+            println!(
+                "Serving {} items, {} recipes on http://{}/api/infinite-craft/pair",
+                rm.num_items(),
+                rm.num_recipes(),
+                server.server_addr()
+            );
+            server::serve(&server, &rm, error_every)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_rate(s: &str) -> Result<f64, String> {
+    let rate: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    // the interval 1 / rate must be a valid duration
+    if rate.is_finite() && rate > 0.0 && Duration::try_from_secs_f64(1.0 / rate).is_ok() {
+        Ok(rate)
+    } else {
+        Err(format!(
+            "{} is not a positive number of requests per second",
+            s
+        ))
+    }
+}
+
+fn parse_backoff(s: &str) -> Result<f64, String> {
+    let backoff: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if Duration::try_from_secs_f64(backoff).is_ok() {
+        Ok(backoff)
+    } else {
+        Err(format!("{} is not a non-negative number of seconds", s))
+    }
+}
+
+#[derive(Deserialize)]
+struct QueuedPair {
+    first: String,
+    second: String,
+}
+
+/// Writes the map through a temporary file, so that an interrupted write keeps the old file.
+fn save(rm: &RecipeMap, path: &Path) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, rm.to_bytes())?;
+    std::fs::rename(&tmp, path).with_context(|| format!("cannot write {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Arc,
+        thread::{self, JoinHandle},
+    };
+
+    use tiny_http::Server;
+
+    use super::*;
+
+    /// Recipes of the stand-in server: a chain from Water and Fire, and an Eclipse recipe of
+    /// items unreachable from them.
+    fn truth() -> Arc<RecipeMap> {
+        let mut truth = RecipeMap::new();
+        truth.insert("Water", "Fire", "Steam");
+        truth.insert("Fire", "Fire", "Volcano");
+        truth.insert("Steam", "Water", "Cloud");
+        truth.insert("Cloud", "Cloud", "Sky");
+        truth.insert("Volcano", "Cloud", "Ash");
+        truth.insert("Sky", "Ash", "Phoenix");
+        truth.insert("Moon", "Sun", "Eclipse");
+        Arc::new(truth)
+    }
+
+    /// Serves `truth` on a free port. Returns the server to unblock, its URL and the thread.
+    fn spawn_server(
+        truth: &Arc<RecipeMap>,
+        error_every: Option<usize>,
+    ) -> (Arc<Server>, String, JoinHandle<anyhow::Result<()>>) {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!(
+            "http://{}/api/infinite-craft/pair",
+            server.server_addr().to_ip().unwrap()
+        );
+        let handle = thread::spawn({
+            let (server, truth) = (server.clone(), truth.clone());
+            move || server::serve(&server, &truth, error_every)
+        });
+        (server, url, handle)
+    }
+
+    #[test]
+    fn crawl_stand_in_server() -> anyhow::Result<()> {
+        let truth = truth();
+        let (server, url, handle) = spawn_server(&truth, Some(4));
+
+        let mut client = Client::new(&url, Duration::ZERO, 2, Duration::from_millis(1));
+        let mut rm = RecipeMap::new();
+        let mut crawler = Crawler::new(&mut rm, &["Water", "Fire"], &url);
+        assert!(crawler.run(&mut rm, &mut client, 5)?);
+        assert_eq!(crawler.stats.requests, 5);
+        assert!(!crawler.run(&mut rm, &mut client, usize::MAX)?);
+        server.unblock();
+        handle.join().unwrap()?;
+
+        let reachable = [
+            "Water", "Fire", "Steam", "Volcano", "Cloud", "Sky", "Ash", "Phoenix",
+        ];
+        assert_eq!(rm.num_items(), reachable.len());
+        assert_eq!(crawler.stats.new_items, reachable.len() - 2);
+        assert_eq!(crawler.stats.requests, 8 * 9 / 2);
+        let source = rm.source_id(&url).unwrap();
+        for first in reachable {
+            for second in reachable {
+                let [u1, u2] = [first, second].map(|u| rm.id(u));
+                let expected = truth.get(truth.id(first), truth.id(second));
+                assert_eq!(
+                    rm.get(u1, u2).map(|u| rm.name(u)),
+                    expected.map(|u| truth.name(u))
+                );
+                assert_eq!(rm.is_nothing(u1, u2), expected.is_none());
+                assert_eq!(rm.provenance(u1, u2).unwrap().source, source);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn crawl_queue() -> anyhow::Result<()> {
+        let truth = truth();
+        let (server, url, handle) = spawn_server(&truth, None);
+
+        let mut client = Client::new(&url, Duration::ZERO, 0, Duration::ZERO);
+        let mut rm = RecipeMap::new();
+        let mut crawler = Crawler::new(&mut rm, &["Water", "Fire"], &url);
+        let queue = [
+            ["Water", "Fire"],
+            ["Fire", "Water"],
+            ["Moon", "Sun"],
+            ["Steam", "Water"],
+            ["Water", "Water"],
+            ["Steam", "Water"],
+            ["Cloud", "Cloud"],
+        ];
+        crawler.set_queue(queue.map(|pair| pair.map(String::from)).to_vec());
+        assert!(!crawler.run(&mut rm, &mut client, usize::MAX)?);
+        server.unblock();
+        handle.join().unwrap()?;
+
+        let stats = crawler.stats;
+        assert_eq!(stats.requests, 4);
+        assert_eq!((stats.recipes, stats.nothing, stats.new_items), (3, 1, 3));
+        assert!(rm.get_id("Moon").is_none());
+        let [water, fire, steam, cloud] = ["Water", "Fire", "Steam", "Cloud"].map(|u| rm.id(u));
+        assert_eq!(rm.get(water, fire), Some(steam));
+        assert_eq!(rm.get(steam, water), Some(cloud));
+        assert!(rm.is_nothing(water, water));
+        assert_eq!(rm.get(cloud, cloud), Some(rm.id("Sky")));
+        assert!(rm.is_untested(fire, fire));
+        Ok(())
+    }
+}
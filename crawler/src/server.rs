@@ -0,0 +1,66 @@
+//! Stand-in for the game server answering from a recipe map.
+
+use std::io::Cursor;
+
+use infinite_craft::RecipeMap;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::api::{PairResponse, NOTHING};
+
+/// Answers `pair` requests until the server is unblocked. Pairs without a recipe give
+/// `NOTHING`. With `error_every`, every so many requests fail with status 503 to exercise the
+/// retries of clients.
+pub fn serve(server: &Server, rm: &RecipeMap, error_every: Option<usize>) -> anyhow::Result<()> {
+    for (i, request) in server.incoming_requests().enumerate() {
+        let response = match error_every {
+            Some(n) if (i + 1) % n == 0 => text(503, "try again"),
+            _ => respond(&request, rm),
+        };
+        request.respond(response)?;
+    }
+    Ok(())
+}
+
+fn respond(request: &Request, rm: &RecipeMap) -> Response<Cursor<Vec<u8>>> {
+    let Ok(url) = url::Url::parse(&format!("http://localhost{}", request.url())) else {
+        return text(400, "invalid url");
+    };
+    if !url.path().ends_with("/pair") {
+        return text(404, "not found");
+    }
+    let arg = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    let (Some(first), Some(second)) = (arg("first"), arg("second")) else {
+        return text(400, "missing first or second");
+    };
+
+    let result = rm
+        .get_id(&first)
+        .zip(rm.get_id(&second))
+        .and_then(|(first, second)| rm.get(first, second));
+    let response = match result {
+        Some(result) => PairResponse {
+            result: rm.name(result).to_string(),
+            emoji: rm
+                .item_info(result)
+                .and_then(|info| info.emoji.clone())
+                .unwrap_or_default(),
+            is_new: false,
+        },
+        None => PairResponse {
+            result: NOTHING.to_string(),
+            emoji: String::new(),
+            is_new: false,
+        },
+    };
+    let json = serde_json::to_string(&response).expect("in-memory serialization cannot fail");
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_string(json).with_header(content_type)
+}
+
+fn text(status: u16, body: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(body).with_status_code(status)
+}
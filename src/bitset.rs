@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::uniform_family::ItemSet;
+
+/// Sparse bitset storing only the non-zero 64-bit words, keyed by word index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BitSet {
+    words: Vec<(u32, u64)>,
+}
+
+impl BitSet {
+    pub fn from_sorted(items: &[u32]) -> Self {
+        let mut words: Vec<(u32, u64)> = Vec::new();
+        for &u in items {
+            let (i, bit) = split(u);
+            match words.last_mut() {
+                Some((j, word)) if *j == i => *word |= bit,
+                _ => words.push((i, bit)),
+            }
+        }
+        Self { words }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().flat_map(|&(i, word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let b = word.trailing_zeros();
+                word &= word - 1;
+                Some(i * 64 + b)
+            })
+        })
+    }
+
+    pub fn max(&self) -> Option<u32> {
+        let &(i, word) = self.words.last()?;
+        Some(i * 64 + 63 - word.leading_zeros())
+    }
+
+    fn insert(&mut self, u: u32) {
+        let (i, bit) = split(u);
+        match self.words.binary_search_by_key(&i, |&(j, _)| j) {
+            Ok(k) => self.words[k].1 |= bit,
+            Err(k) => self.words.insert(k, (i, bit)),
+        }
+    }
+
+    /// Calls `f` with the index and both words of every word present in either set.
+    fn zip_words(&self, other: &Self, mut f: impl FnMut(u32, u64, u64)) {
+        let mut iter1 = self.words.iter().peekable();
+        let mut iter2 = other.words.iter().peekable();
+        loop {
+            match (iter1.peek(), iter2.peek()) {
+                (Some(&&(i1, w1)), Some(&&(i2, w2))) => match i1.cmp(&i2) {
+                    Ordering::Less => {
+                        f(i1, w1, 0);
+                        iter1.next();
+                    }
+                    Ordering::Greater => {
+                        f(i2, 0, w2);
+                        iter2.next();
+                    }
+                    Ordering::Equal => {
+                        f(i1, w1, w2);
+                        iter1.next();
+                        iter2.next();
+                    }
+                },
+                (Some(&&(i1, w1)), None) => {
+                    f(i1, w1, 0);
+                    iter1.next();
+                }
+                (None, Some(&&(i2, w2))) => {
+                    f(i2, 0, w2);
+                    iter2.next();
+                }
+                (None, None) => break,
+            }
+        }
+    }
+}
+
+#[inline]
+fn split(u: u32) -> (u32, u64) {
+    (u / 64, 1 << (u % 64))
+}
+
+impl ItemSet for BitSet {
+    fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|&(_, word)| word.count_ones() as usize)
+            .sum()
+    }
+
+    fn contains(&self, u: u32) -> bool {
+        let (i, bit) = split(u);
+        match self.words.binary_search_by_key(&i, |&(j, _)| j) {
+            Ok(k) => self.words[k].1 & bit != 0,
+            Err(_) => false,
+        }
+    }
+
+    fn union_len(&self, other: &Self) -> usize {
+        let mut len = 0;
+        self.zip_words(other, |_, w1, w2| len += (w1 | w2).count_ones() as usize);
+        len
+    }
+
+    fn union_with(&self, other: &Self, u: u32) -> Self {
+        let mut words = Vec::with_capacity(self.words.len() + other.words.len() + 1);
+        self.zip_words(other, |i, w1, w2| words.push((i, w1 | w2)));
+        let mut set = Self { words };
+        set.insert(u);
+        set
+    }
+
+    fn to_vec(&self) -> Vec<u32> {
+        self.iter().collect()
+    }
+
+    fn heap_size(&self) -> usize {
+        self.words.capacity() * std::mem::size_of::<(u32, u64)>()
+    }
+}
+
+impl PartialOrd for BitSet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Lexicographic order of the sorted item ids, the same as `Vec<u32>`.
+impl Ord for BitSet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // the smallest item in the symmetric difference decides, unless the other set has
+        // nothing after it and is therefore a prefix
+        let mut first_diff = None;
+        self.zip_words(other, |i, w1, w2| {
+            if first_diff.is_none() && w1 != w2 {
+                let b = (w1 ^ w2).trailing_zeros();
+                first_diff = Some((i * 64 + b, w1 >> b & 1 == 1));
+            }
+        });
+        match first_diff {
+            None => Ordering::Equal,
+            Some((u, true)) if other.max().is_none_or(|m| m < u) => Ordering::Greater,
+            Some((_, true)) => Ordering::Less,
+            Some((u, false)) if self.max().is_none_or(|m| m < u) => Ordering::Less,
+            Some((_, false)) => Ordering::Greater,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn bitset_agrees_with_vec() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let random_set = |rng: &mut StdRng| {
+            let len = rng.gen_range(0..8);
+            let mut set: Vec<u32> = (0..len).map(|_| rng.gen_range(0..150)).collect();
+            set.sort();
+            set.dedup();
+            set
+        };
+        for _ in 0..10000 {
+            let v1 = random_set(&mut rng);
+            let v2 = random_set(&mut rng);
+            let u = rng.gen_range(0..150);
+            let b1 = BitSet::from_sorted(&v1);
+            let b2 = BitSet::from_sorted(&v2);
+            assert_eq!(b1.to_vec(), v1);
+            assert_eq!(ItemSet::len(&b1), v1.len());
+            assert_eq!(b1.contains(u), v1.as_slice().contains(&u));
+            assert_eq!(b1.union_len(&b2), v1.union_len(&v2));
+            assert_eq!(b1.union_with(&b2, u).to_vec(), v1.union_with(&v2, u));
+            assert_eq!(b1.cmp(&b2), v1.cmp(&v2), "{:?} {:?}", v1, v2);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Provenance;
+
+/// How `RecipeMap` chooses the result of a pair when sources disagree.
+///
+/// Every policy except `FirstWins` depends only on the set of claims, so the resulting map does
+/// not depend on the order the sources are read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// Keep the result inserted first.
+    #[default]
+    FirstWins,
+    /// Prefer the claim whose source comes first in the list. Claims from other sources or of
+    /// unknown provenance come last, in insertion order.
+    SourcePriority(Vec<u32>),
+    /// Take the result claimed most often. Ties leave the pair without a recipe.
+    MajorityVote,
+    /// Leave every conflicting pair without a recipe.
+    RejectBoth,
+}
+
+/// A result some source gave for a pair.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claim {
+    pub result: String,
+    pub provenance: Option<Provenance>,
+}
+
+/// Pair with claims of different results.
+#[derive(Debug, Clone, Copy)]
+pub struct Conflict<'a> {
+    /// Item ids with the smaller id first.
+    pub pair: [u32; 2],
+    /// Claims in insertion order.
+    pub claims: &'a [Claim],
+    /// Result chosen by the policy, if any.
+    pub result: Option<u32>,
+}
+
+impl ConflictPolicy {
+    /// Index of the winning claim.
+    pub fn choose(&self, claims: &[Claim]) -> Option<usize> {
+        match self {
+            Self::FirstWins => (!claims.is_empty()).then_some(0),
+            Self::SourcePriority(order) => (0..claims.len()).min_by_key(|&i| {
+                claims[i]
+                    .provenance
+                    .and_then(|p| order.iter().position(|&s| s == p.source))
+                    .unwrap_or(usize::MAX)
+            }),
+            Self::MajorityVote => {
+                let mut votes: HashMap<&str, usize> = HashMap::new();
+                for claim in claims {
+                    *votes.entry(&claim.result).or_default() += 1;
+                }
+                let max = votes.values().copied().max()?;
+                let mut winners = votes.iter().filter(|&(_, &n)| n == max);
+                let (&winner, _) = winners.next()?;
+                if winners.next().is_some() {
+                    return None;
+                }
+                claims.iter().position(|claim| claim.result == winner)
+            }
+            Self::RejectBoth => {
+                let first = claims.first()?;
+                claims
+                    .iter()
+                    .all(|claim| claim.result == first.result)
+                    .then_some(0)
+            }
+        }
+    }
+}
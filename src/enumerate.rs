@@ -0,0 +1,284 @@
+//! Lazy enumeration of every minimum-cardinality set crafting a target.
+//!
+//! `Search` keeps at most `max_count` sets per item, so the alternatives it reports may be an
+//! arbitrary subset. `MinSets` instead backtracks over the recipes of `Graph::arcs_to`, deciding
+//! for one item at a time whether it is in the set, so that each set is yielded exactly once and
+//! only the current branch is held in memory.
+
+use crate::{
+    exact::{compute_level, craft},
+    find_path, Graph, Recipe, RecipeMap,
+};
+
+/// Iterator over the sets of minimum cardinality crafting a target, each sorted by id.
+///
+/// The sets are yielded in the order of the backtracking, which depends only on the graph, so
+/// `take(n)` always gives the same first `n` sets.
+pub struct MinSets<'a> {
+    graph: &'a Graph,
+    source: Vec<u32>,
+    is_source: Vec<bool>,
+    level: Vec<usize>,
+    target: u32,
+    card: usize,
+    max_card: usize,
+    /// Items decided to be in the set, the target first.
+    set: Vec<u32>,
+    /// Index of each item in `set`, `usize::MAX` for the items not in it.
+    position: Vec<usize>,
+    excluded: Vec<bool>,
+    /// Decided items with whether they are in the set, in the order they were decided.
+    decisions: Vec<(u32, bool)>,
+    found: bool,
+    done: bool,
+}
+
+enum Node {
+    Leaf,
+    Dead,
+    Branch(u32),
+}
+
+impl<'a> MinSets<'a> {
+    /// Enumerates the sets of the smallest cardinality up to `max_card` crafting `target`. Pass
+    /// `Search::min_card` as `max_card`, since the heuristic search gives an upper bound.
+    pub fn new(source: &[u32], graph: &'a Graph, target: u32, max_card: usize) -> Self {
+        let n = graph.num_vertices();
+        let mut is_source = vec![false; n];
+        for &u in source {
+            is_source[u as usize] = true;
+        }
+        let level = compute_level(source, graph);
+        let card = level[target as usize];
+        let mut sets = Self {
+            graph,
+            source: source.to_vec(),
+            is_source,
+            level,
+            target,
+            card,
+            max_card,
+            set: Vec::new(),
+            position: vec![usize::MAX; n],
+            excluded: vec![false; n],
+            decisions: Vec::new(),
+            found: false,
+            done: card == usize::MAX || card > max_card,
+        };
+        sets.include(target);
+        sets
+    }
+
+    /// Cardinality of the sets currently enumerated. It is the minimum once a set was yielded.
+    pub fn card(&self) -> usize {
+        self.card
+    }
+
+    /// Lower bound of the cardinality of any set crafting `u`, to order the items of a set
+    /// for `find_path`.
+    #[inline]
+    pub fn level(&self, u: u32) -> usize {
+        self.level[u as usize]
+    }
+
+    /// Paths of the sets, in the same order.
+    pub fn paths(mut self, rm: &'a RecipeMap) -> impl Iterator<Item = Vec<Recipe>> + 'a {
+        let source = std::mem::take(&mut self.source);
+        let level = self.level.clone();
+        self.filter_map(move |mut set| {
+            set.sort_by_key(|&u| level[u as usize]);
+            find_path(&source, &set, rm)
+        })
+    }
+
+    fn include(&mut self, u: u32) {
+        self.position[u as usize] = self.set.len();
+        self.set.push(u);
+    }
+
+    /// Whether `u` may be an input of a recipe of the set: a source, or an item that may be in
+    /// a set of `card` items with the target.
+    fn is_possible(&self, u: u32) -> bool {
+        self.is_source[u as usize] || !self.excluded[u as usize] && self.level(u) < self.card
+    }
+
+    fn is_undecided(&self, u: u32) -> bool {
+        !self.is_source[u as usize]
+            && self.position[u as usize] == usize::MAX
+            && !self.excluded[u as usize]
+    }
+
+    fn feasible_arcs(&self, u3: u32) -> impl Iterator<Item = [u32; 2]> + '_ {
+        self.graph
+            .arcs_to(u3)
+            .filter(move |&[u1, u2]| u1 != u3 && u2 != u3)
+            .filter(|&[u1, u2]| self.is_possible(u1) && self.is_possible(u2))
+    }
+
+    fn expand(&self) -> Node {
+        if self.set.len() > self.card {
+            return Node::Dead;
+        }
+        let crafted = craft(self.graph, &self.is_source, &self.set, &self.position);
+        let mut uncrafted: Vec<u32> = (0..self.set.len())
+            .filter(|&i| !crafted[i])
+            .map(|i| self.set[i])
+            .collect();
+        if uncrafted.is_empty() {
+            return if self.set.len() == self.card {
+                Node::Leaf
+            } else {
+                Node::Dead
+            };
+        }
+
+        // every uncrafted item needs the undecided inputs of one of its recipes
+        let mut min_new = 0;
+        for &u3 in &uncrafted {
+            let new = self
+                .feasible_arcs(u3)
+                .map(|[u1, u2]| {
+                    self.is_undecided(u1) as usize + (u1 != u2 && self.is_undecided(u2)) as usize
+                })
+                .min();
+            match new {
+                Some(new) => min_new = min_new.max(new),
+                None => return Node::Dead,
+            }
+        }
+        if self.set.len() + min_new > self.card {
+            return Node::Dead;
+        }
+
+        // decide an input of the item hardest to craft first to fail early
+        uncrafted.sort_by_key(|&u| (std::cmp::Reverse(self.level(u)), u));
+        uncrafted
+            .iter()
+            .find_map(|&u3| {
+                self.feasible_arcs(u3)
+                    .flatten()
+                    .find(|&u| self.is_undecided(u))
+            })
+            .map_or(Node::Dead, Node::Branch)
+    }
+
+    /// Moves to the next branch: excludes the last item decided to be in the set, undoing the
+    /// decisions after it. Returns false when every branch is done.
+    fn backtrack(&mut self) -> bool {
+        while let Some((u, included)) = self.decisions.pop() {
+            if included {
+                self.set.pop();
+                self.position[u as usize] = usize::MAX;
+                self.excluded[u as usize] = true;
+                self.decisions.push((u, false));
+                return true;
+            }
+            self.excluded[u as usize] = false;
+        }
+        false
+    }
+
+    /// Restarts with the next cardinality if no set of the current one exists.
+    fn next_card(&mut self) -> bool {
+        if self.found || self.card >= self.max_card {
+            self.done = true;
+            return false;
+        }
+        self.card += 1;
+        true
+    }
+}
+
+impl Iterator for MinSets<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Vec<u32>> {
+        if self.is_source[self.target as usize] {
+            let done = std::mem::replace(&mut self.done, true);
+            return (!done).then(Vec::new);
+        }
+        while !self.done {
+            match self.expand() {
+                Node::Branch(u) => {
+                    self.decisions.push((u, true));
+                    self.include(u);
+                }
+                Node::Leaf => {
+                    let mut set = self.set.clone();
+                    set.sort();
+                    self.found = true;
+                    if !self.backtrack() {
+                        self.next_card();
+                    }
+                    return Some(set);
+                }
+                Node::Dead => {
+                    if !self.backtrack() {
+                        self.next_card();
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{random_recipe_map, search::Search};
+
+    use super::*;
+
+    #[test]
+    fn min_sets_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let rm = random_recipe_map(&mut rng, 10, 30);
+            let graph = rm.make_graph();
+            let source: Vec<u32> = rm.items().take(3).collect();
+            let mut search = Search::new(rm.num_items(), 1, usize::MAX);
+            search.search_from_source(&source, &graph);
+
+            for u in rm.items() {
+                let sets: Vec<_> = MinSets::new(&source, &graph, u, search.min_card(u)).collect();
+                let mut sorted = sets.clone();
+                sorted.sort();
+                assert_eq!(sorted, brute_force(&source, u, &rm), "{}", rm.name(u));
+                if search.reached(u) {
+                    assert!(!sets.is_empty());
+                }
+                let first: Vec<_> = MinSets::new(&source, &graph, u, search.min_card(u))
+                    .take(2)
+                    .collect();
+                assert_eq!(first[..], sets[..sets.len().min(2)]);
+                let paths = MinSets::new(&source, &graph, u, search.min_card(u)).paths(&rm);
+                assert_eq!(paths.count(), sets.len());
+            }
+        }
+    }
+
+    /// Every minimum set crafting the target, sorted.
+    fn brute_force(source: &[u32], target: u32, rm: &RecipeMap) -> Vec<Vec<u32>> {
+        if source.contains(&target) {
+            return vec![vec![]];
+        }
+        let items: Vec<u32> = rm.items().filter(|u| !source.contains(u)).collect();
+        let mut sets: Vec<Vec<u32>> = (0u32..1 << items.len())
+            .map(|mask| {
+                (0..items.len())
+                    .filter(|&i| mask >> i & 1 == 1)
+                    .map(|i| items[i])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|set| set.contains(&target) && find_path(source, set, rm).is_some())
+            .collect();
+        let Some(min) = sets.iter().map(|set| set.len()).min() else {
+            return vec![];
+        };
+        sets.retain(|set| set.len() == min);
+        sets.sort();
+        sets
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+
+use crate::Graph;
+
+/// Proof that no set smaller than `min_card` crafts the target.
+///
+/// Every set of cardinality below `min_card` was either pruned or rejected by the
+/// exhaustive backward search over the recipes of the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    pub min_card: usize,
+    pub level: usize,
+    pub num_nodes: u64,
+    pub num_vertices: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExactResult {
+    pub set: Option<Vec<u32>>,
+    pub certificate: Certificate,
+}
+
+pub struct ExactSearch<'a> {
+    graph: &'a Graph,
+    is_source: Vec<bool>,
+    level: Vec<usize>,
+    /// Index of each item in the set checked by `is_craftable`, `usize::MAX` for the others.
+    position: Vec<usize>,
+    failed: HashSet<(Vec<u32>, Vec<u32>)>,
+    num_nodes: u64,
+}
+
+impl<'a> ExactSearch<'a> {
+    pub fn new(source: &[u32], graph: &'a Graph) -> Self {
+        let mut is_source = vec![false; graph.num_vertices()];
+        for &u in source {
+            is_source[u as usize] = true;
+        }
+        Self {
+            graph,
+            level: compute_level(source, graph),
+            is_source,
+            position: vec![usize::MAX; graph.num_vertices()],
+            failed: HashSet::new(),
+            num_nodes: 0,
+        }
+    }
+
+    #[inline]
+    pub fn level(&self, u: u32) -> usize {
+        self.level[u as usize]
+    }
+
+    /// Finds a minimum-cardinality set crafting `target`, trying every cardinality up to
+    /// `max_card`. Pass `Search::min_card` as `max_card` to use the heuristic as an upper bound.
+    pub fn solve(&mut self, target: u32, max_card: usize) -> ExactResult {
+        self.num_nodes = 0;
+        let level = self.level(target);
+        if self.is_source[target as usize] {
+            return self.result(Some(vec![]), 0, 0);
+        }
+
+        if level == usize::MAX {
+            return self.result(None, usize::MAX, level);
+        }
+        for card in level..=max_card {
+            self.failed.clear();
+            let mut set = vec![target];
+            let mut pending = vec![target];
+            if self.dfs(&mut set, &mut pending, card) {
+                set.sort();
+                return self.result(Some(set), card, level);
+            }
+        }
+        self.result(None, max_card.saturating_add(1), level)
+    }
+
+    fn result(&self, set: Option<Vec<u32>>, min_card: usize, level: usize) -> ExactResult {
+        ExactResult {
+            set,
+            certificate: Certificate {
+                min_card,
+                level,
+                num_nodes: self.num_nodes,
+                num_vertices: self.graph.num_vertices(),
+            },
+        }
+    }
+
+    fn dfs(&mut self, set: &mut Vec<u32>, pending: &mut Vec<u32>, card: usize) -> bool {
+        self.num_nodes += 1;
+        if pending.is_empty() {
+            return self.is_craftable(set);
+        }
+        if set.len().saturating_add(self.min_new_inputs(set, pending)) > card {
+            return false;
+        }
+        let key = {
+            let mut s = set.clone();
+            let mut p = pending.clone();
+            s.sort();
+            p.sort();
+            (s, p)
+        };
+        if self.failed.contains(&key) {
+            return false;
+        }
+
+        // expand the pending item hardest to craft first to fail early
+        let i = (0..pending.len())
+            .max_by_key(|&i| (self.level(pending[i]), pending[i]))
+            .unwrap();
+        let u3 = pending.swap_remove(i);
+
+        let set_len = set.len();
+        let pending_len = pending.len();
+        for [u1, u2] in self.graph.arcs_to(u3) {
+            if u1 == u3 || u2 == u3 || self.level(u1).max(self.level(u2)) >= card {
+                continue;
+            }
+            for u in [u1, u2] {
+                if !self.is_source[u as usize] && !set.contains(&u) {
+                    set.push(u);
+                    pending.push(u);
+                }
+            }
+            if set.len() <= card && self.dfs(set, pending, card) {
+                return true;
+            }
+            set.truncate(set_len);
+            pending.truncate(pending_len);
+        }
+
+        pending.push(u3);
+        let last = pending.len() - 1;
+        pending.swap(i, last);
+        self.failed.insert(key);
+        false
+    }
+
+    fn min_new_inputs(&self, set: &[u32], pending: &[u32]) -> usize {
+        let is_new = |u: u32| (!self.is_source[u as usize] && !set.contains(&u)) as usize;
+        pending
+            .iter()
+            .map(|&u3| {
+                self.graph
+                    .arcs_to(u3)
+                    .map(|[u1, u2]| is_new(u1) + (u1 != u2) as usize * is_new(u2))
+                    .min()
+                    .unwrap_or(usize::MAX)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn is_craftable(&mut self, set: &[u32]) -> bool {
+        for (i, &u) in set.iter().enumerate() {
+            self.position[u as usize] = i;
+        }
+        let crafted = craft(self.graph, &self.is_source, set, &self.position);
+        for &u in set {
+            self.position[u as usize] = usize::MAX;
+        }
+        crafted.iter().all(|&c| c)
+    }
+}
+
+/// Whether each item of `set` is craftable from the sources and the other items of the set.
+/// `position[u]` is the index of `u` in `set`, `usize::MAX` if it is not in it.
+pub(crate) fn craft(
+    graph: &Graph,
+    is_source: &[bool],
+    set: &[u32],
+    position: &[usize],
+) -> Vec<bool> {
+    let mut crafted = vec![false; set.len()];
+    let available = |u: u32, crafted: &[bool]| {
+        is_source[u as usize] || crafted.get(position[u as usize]).copied().unwrap_or(false)
+    };
+    let mut updated = true;
+    while updated {
+        updated = false;
+        for (i, &u3) in set.iter().enumerate() {
+            if !crafted[i]
+                && graph
+                    .arcs_to(u3)
+                    .any(|[u1, u2]| available(u1, &crafted) && available(u2, &crafted))
+            {
+                crafted[i] = true;
+                updated = true;
+            }
+        }
+    }
+    crafted
+}
+
+/// Number of recipe steps on the longest chain of the shallowest crafting tree of each item,
+/// which is a lower bound of the cardinality of any set crafting it.
+pub(crate) fn compute_level(source: &[u32], graph: &Graph) -> Vec<usize> {
+    let mut level = vec![usize::MAX; graph.num_vertices()];
+    let mut current = Vec::new();
+    for &u in source {
+        level[u as usize] = 0;
+        current.push(u);
+    }
+    let mut l = 0;
+    while !current.is_empty() {
+        let mut next = Vec::new();
+        for &u1 in &current {
+            for (u2, u3) in graph.arcs_from(u1) {
+                if level[u2 as usize] <= l && level[u3 as usize] == usize::MAX {
+                    level[u3 as usize] = l + 1;
+                    next.push(u3);
+                }
+            }
+        }
+        current = next;
+        l += 1;
+    }
+    level
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{find_path, random_recipe_map, search::Search, RecipeMap};
+
+    use super::*;
+
+    #[test]
+    fn exact_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..50 {
+            let rm = random_recipe_map(&mut rng, 12, 30);
+            let graph = rm.make_graph();
+            let source: Vec<u32> = rm.items().take(3).collect();
+
+            let mut search = Search::new(rm.num_items(), usize::MAX, usize::MAX);
+            search.search_from_source(&source, &graph);
+            let mut exact = ExactSearch::new(&source, &graph);
+            for u in rm.items() {
+                let result = exact.solve(u, search.min_card(u));
+                let expected = brute_force(&source, u, &mut exact);
+                assert_eq!(
+                    result.set.as_ref().map(|s| s.len()),
+                    expected,
+                    "{}",
+                    rm.name(u)
+                );
+                if let Some(set) = result.set {
+                    assert_eq!(result.certificate.min_card, set.len());
+                    assert!(set.len() <= search.min_card(u));
+                    assert!(find_path(&source, &set, &rm).is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exact_improves_on_search() {
+        let mut rm = RecipeMap::new();
+        for [first, second, result] in [
+            ["Water", "Water", "X"],
+            ["Fire", "Fire", "Y"],
+            ["Water", "Fire", "P"],
+            ["Earth", "Earth", "Q"],
+            ["Y", "Water", "Z"],
+            ["X", "Fire", "A"],
+            ["P", "Water", "B"],
+            ["Q", "Water", "C"],
+            ["Y", "Z", "A"],
+            ["Z", "Z", "B"],
+            ["Z", "Fire", "C"],
+            ["A", "B", "T"],
+            ["T", "C", "U"],
+        ] {
+            rm.insert(first, second, result);
+        }
+        let graph = rm.make_graph();
+        let source = ["Water", "Fire", "Earth"].map(|u| rm.id(u));
+        let target = rm.id("U");
+
+        let mut search = Search::new(rm.num_items(), usize::MAX, usize::MAX);
+        search.search_from_source(&source, &graph);
+        assert_eq!(search.min_card(target), 8);
+
+        let result = ExactSearch::new(&source, &graph).solve(target, search.min_card(target));
+        let mut expected = ["Y", "Z", "A", "B", "C", "T", "U"].map(|u| rm.id(u));
+        expected.sort();
+        assert_eq!(result.set.as_deref(), Some(&expected[..]));
+        assert_eq!(result.certificate.min_card, 7);
+    }
+
+    fn brute_force(source: &[u32], target: u32, exact: &mut ExactSearch) -> Option<usize> {
+        if source.contains(&target) {
+            return Some(0);
+        }
+        let items: Vec<u32> = (0..exact.graph.num_vertices() as u32)
+            .filter(|u| !source.contains(u))
+            .collect();
+        (0u32..1 << items.len())
+            .map(|mask| {
+                (0..items.len())
+                    .filter(|&i| mask >> i & 1 == 1)
+                    .map(|i| items[i])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|set| set.contains(&target) && exact.is_craftable(set))
+            .map(|set| set.len())
+            .min()
+    }
+}
@@ -0,0 +1,117 @@
+//! Diagrams of the paths of `find_path`.
+//!
+//! Every item is one node, so an intermediate used by several recipes is drawn once, with an
+//! edge from each input of a recipe to its result. Source items are filled and the target has a
+//! bold double border.
+
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{Recipe, RecipeMap};
+
+/// Graphviz DOT digraph of the path, drawn from the sources at the top to the target.
+pub fn to_dot(path: &[Recipe], source: &[u32], target: u32, rm: &RecipeMap) -> String {
+    let mut out = String::from("digraph path {\n    rankdir=TB;\n    node [shape=box];\n");
+    for u in items(path) {
+        let style = if u == target {
+            ", peripheries=2, style=bold"
+        } else if source.contains(&u) {
+            ", style=filled, fillcolor=lightblue"
+        } else {
+            ""
+        };
+        let label = label(u, rm).replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(out, "    n{} [label=\"{}\"{}];", u, label, style).unwrap();
+    }
+    for r in path {
+        for u in r.pair {
+            writeln!(out, "    n{} -> n{};", u, r.result).unwrap();
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Mermaid flowchart of the path, drawn from the sources at the top to the target.
+pub fn to_mermaid(path: &[Recipe], source: &[u32], target: u32, rm: &RecipeMap) -> String {
+    let mut out = String::from("flowchart TD\n");
+    for u in items(path) {
+        let label = label(u, rm).replace('"', "#quot;");
+        writeln!(out, "    n{}[\"{}\"]", u, label).unwrap();
+    }
+    for r in path {
+        for u in r.pair {
+            writeln!(out, "    n{} --> n{}", u, r.result).unwrap();
+        }
+    }
+    out.push_str("    classDef source fill:#add8e6\n");
+    out.push_str("    classDef target stroke-width:4px,font-weight:bold\n");
+    let sources: Vec<_> = items(path)
+        .filter(|&u| u != target && source.contains(&u))
+        .map(|u| format!("n{}", u))
+        .collect();
+    if !sources.is_empty() {
+        writeln!(out, "    class {} source", sources.join(",")).unwrap();
+    }
+    writeln!(out, "    class n{} target", target).unwrap();
+    out
+}
+
+/// Items of the path in the order they first appear.
+fn items(path: &[Recipe]) -> impl Iterator<Item = u32> + '_ {
+    let mut seen = HashSet::new();
+    path.iter()
+        .flat_map(|r| [r.pair[0], r.pair[1], r.result])
+        .filter(move |&u| seen.insert(u))
+}
+
+fn label(u: u32, rm: &RecipeMap) -> String {
+    match rm.item_info(u).and_then(|info| info.emoji.as_deref()) {
+        Some(emoji) => format!("{} {}", emoji, rm.name(u)),
+        None => rm.name(u).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Ids are Fire=0, Water=1, Steam=2, Engine=3 and Train=4.
+    fn example() -> (RecipeMap, Vec<Recipe>) {
+        let mut rm = RecipeMap::new();
+        rm.insert("Water", "Fire", "Steam");
+        rm.insert("Steam", "Fire", "Engine");
+        rm.insert("Engine", "Steam", "Train \"Express\"");
+        let [water, fire, steam, engine, train] =
+            ["Water", "Fire", "Steam", "Engine", "Train \"Express\""].map(|u| rm.id(u));
+        let path = vec![
+            Recipe::new(water, fire, steam),
+            Recipe::new(steam, fire, engine),
+            Recipe::new(engine, steam, train),
+        ];
+        (rm, path)
+    }
+
+    #[test]
+    fn dot_draws_each_item_once() {
+        let (rm, path) = example();
+        let dot = to_dot(&path, &[0, 1], 4, &rm);
+        assert!(dot.starts_with("digraph path {\n"));
+        assert_eq!(dot.matches("n2 [label=\"Steam\"]").count(), 1);
+        assert_eq!(dot.matches(" -> ").count(), 6);
+        assert!(dot.contains("n2 -> n4;"));
+        assert!(dot.contains("n1 [label=\"Water\", style=filled"));
+        assert!(dot.contains("n4 [label=\"Train \\\"Express\\\"\", peripheries=2"));
+    }
+
+    #[test]
+    fn mermaid_draws_each_item_once() {
+        let (rm, path) = example();
+        let mermaid = to_mermaid(&path, &[0, 1], 4, &rm);
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert_eq!(mermaid.matches("n2[\"Steam\"]").count(), 1);
+        assert_eq!(mermaid.matches(" --> ").count(), 6);
+        assert!(mermaid.contains("n4[\"Train #quot;Express#quot;\"]"));
+        assert!(mermaid.contains("class n1,n0 source"));
+        assert!(mermaid.contains("class n4 target"));
+    }
+}
@@ -0,0 +1,174 @@
+//! Container of the recipe map file.
+//!
+//! A file is `MAGIC`, the format version as a little-endian `u32` and a sequence of sections.
+//! Each section has a 4-byte tag, flags as `u32`, the payload length as `u64`, the FNV-1a hash of
+//! the payload as `u64` and the payload itself. Readers skip optional sections they do not know,
+//! so new optional data can be added without a version bump.
+//!
+//! Since version 2 each payload is preceded by zero padding to start at a multiple of `ALIGN`
+//! from the beginning of the file, so that arrays can be used in place from a memory map.
+
+use anyhow::{bail, ensure, Context};
+
+use crate::Fnv1a;
+
+pub const MAGIC: &[u8; 8] = b"ICRECMAP";
+pub const VERSION: u32 = 2;
+pub const ALIGN: usize = 8;
+
+/// The reader must understand the section to use the file.
+pub const REQUIRED: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section<'a> {
+    pub tag: [u8; 4],
+    pub flags: u32,
+    pub payload: &'a [u8],
+}
+
+impl Section<'_> {
+    pub fn is_required(&self) -> bool {
+        self.flags & REQUIRED != 0
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.tag).into_owned()
+    }
+}
+
+pub fn is_versioned(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn write(sections: &[Section]) -> Vec<u8> {
+    let len = sections
+        .iter()
+        .map(|s| 24 + ALIGN + s.payload.len())
+        .sum::<usize>();
+    let mut out = Vec::with_capacity(MAGIC.len() + 4 + len);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    for section in sections {
+        out.extend_from_slice(&section.tag);
+        out.extend_from_slice(&section.flags.to_le_bytes());
+        out.extend_from_slice(&(section.payload.len() as u64).to_le_bytes());
+        out.extend_from_slice(&checksum(section.payload).to_le_bytes());
+        out.resize(out.len().next_multiple_of(ALIGN), 0);
+        out.extend_from_slice(section.payload);
+    }
+    out
+}
+
+/// Splits a file into its sections after checking the header and every checksum. Fails on
+/// required sections whose tag is not in `known`, and drops unknown optional ones.
+pub fn read<'a>(data: &'a [u8], known: &[[u8; 4]]) -> anyhow::Result<(u32, Vec<Section<'a>>)> {
+    read_sections(data, known, true)
+}
+
+/// Like `read` but without computing the checksums, which would touch every page of a memory
+/// mapped file.
+pub fn read_unchecked<'a>(
+    data: &'a [u8],
+    known: &[[u8; 4]],
+) -> anyhow::Result<(u32, Vec<Section<'a>>)> {
+    read_sections(data, known, false)
+}
+
+fn read_sections<'a>(
+    data: &'a [u8],
+    known: &[[u8; 4]],
+    verify: bool,
+) -> anyhow::Result<(u32, Vec<Section<'a>>)> {
+    ensure!(is_versioned(data), "missing recipe map header");
+    let mut reader = Reader {
+        data: &data[MAGIC.len()..],
+        pos: MAGIC.len(),
+    };
+    let version = u32::from_le_bytes(reader.take("header")?);
+    ensure!(
+        version <= VERSION,
+        "file format version {} is newer than the supported version {}; \
+         update infinite-craft or regenerate the file with this version of combine-recipes",
+        version,
+        VERSION
+    );
+
+    let mut sections = Vec::new();
+    while !reader.data.is_empty() {
+        let tag: [u8; 4] = reader.take("section header")?;
+        let flags = u32::from_le_bytes(reader.take("section header")?);
+        let len = u64::from_le_bytes(reader.take("section header")?);
+        let expected = u64::from_le_bytes(reader.take("section header")?);
+        if version >= 2 {
+            reader.align(&tag)?;
+        }
+        let section = Section {
+            tag,
+            flags,
+            payload: reader.take_slice(len, &tag)?,
+        };
+        if verify && checksum(section.payload) != expected {
+            bail!(
+                "checksum mismatch in section {:?}; the file is corrupt, regenerate it with \
+                 combine-recipes",
+                section.name()
+            );
+        }
+        if known.contains(&tag) {
+            sections.push(section);
+        } else if section.is_required() {
+            bail!(
+                "unknown required section {:?}; the file was written by a newer version, \
+                 update infinite-craft",
+                section.name()
+            );
+        }
+    }
+    Ok((version, sections))
+}
+
+fn checksum(payload: &[u8]) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(payload);
+    hash.0
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    /// Offset of `data` from the beginning of the file.
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self, what: &str) -> anyhow::Result<[u8; N]> {
+        let bytes = self.data.get(..N).with_context(|| truncated(what))?;
+        self.advance(N);
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn take_slice(&mut self, len: u64, tag: &[u8; 4]) -> anyhow::Result<&'a [u8]> {
+        let what = || truncated(&format!("section {:?}", String::from_utf8_lossy(tag)));
+        let len = usize::try_from(len).ok().with_context(what)?;
+        let bytes = self.data.get(..len).with_context(what)?;
+        self.advance(len);
+        Ok(bytes)
+    }
+
+    fn align(&mut self, tag: &[u8; 4]) -> anyhow::Result<()> {
+        let padding = self.pos.next_multiple_of(ALIGN) - self.pos;
+        self.take_slice(padding as u64, tag)?;
+        Ok(())
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.data = &self.data[len..];
+        self.pos += len;
+    }
+}
+
+fn truncated(what: &str) -> String {
+    format!(
+        "file truncated in {}; regenerate it with combine-recipes",
+        what
+    )
+}
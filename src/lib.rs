@@ -1,7 +1,30 @@
+pub mod bitset;
+pub mod conflict;
+pub mod enumerate;
+pub mod exact;
+pub mod export;
+pub mod format;
+mod mapped;
+pub mod names;
+pub mod plan;
+pub mod progress;
 pub mod search;
+pub mod solution;
 pub mod uniform_family;
 
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    mem::replace,
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{bail, ensure, Context};
+use conflict::{Claim, Conflict, ConflictPolicy};
+use mapped::{Array, Frozen, ProvenanceRecord};
+use names::{NameIndex, Normalization};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Recipe {
@@ -18,10 +41,83 @@ impl Recipe {
     }
 }
 
+/// Where a recipe was learned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Index of the source name in the `RecipeMap`.
+    pub source: u32,
+    /// Seconds since the Unix epoch, if the source records it.
+    pub timestamp: Option<u64>,
+}
+
+impl Provenance {
+    pub fn new(source: u32, timestamp: Option<u64>) -> Self {
+        Self { source, timestamp }
+    }
+}
+
+/// What a source tells about an item besides its recipes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemInfo {
+    pub emoji: Option<String>,
+    /// Whether the item was a first discovery in the game, that is its source made it first.
+    pub first_discovery: bool,
+    /// Seconds since the Unix epoch when the source first saw the item.
+    pub first_seen: Option<u64>,
+    /// Index of the name of the source of `first_seen`, `None` without it. The emoji and
+    /// `first_discovery` may come from other sources.
+    pub source: Option<u32>,
+}
+
+impl ItemInfo {
+    /// Adds what `other` knows and `self` does not: the first emoji is kept, an item is a first
+    /// discovery if any source says so, and the earlier first sighting wins with its source.
+    fn merge(&mut self, other: ItemInfo) {
+        if self.emoji.is_none() {
+            self.emoji = other.emoji;
+        }
+        self.first_discovery |= other.first_discovery;
+        let earlier = match (self.first_seen, other.first_seen) {
+            (Some(t), Some(other_t)) => other_t < t,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if earlier {
+            self.first_seen = other.first_seen;
+            self.source = other.source;
+        }
+    }
+}
+
+pub type Serialized<'a> = (
+    Vec<&'a str>,
+    Vec<([u32; 2], u32)>,
+    Vec<&'a str>,
+    Vec<([u32; 2], Provenance)>,
+    Vec<[u32; 2]>,
+);
+
 pub struct RecipeMap {
     names: Vec<String>,
     id: HashMap<String, u32>,
     map: HashMap<[u32; 2], u32>,
+    sources: Vec<String>,
+    /// Sources of the recipes and of the nothing results. Keyed by the pair with the smaller id
+    /// first.
+    provenance: HashMap<[u32; 2], Provenance>,
+    policy: ConflictPolicy,
+    /// Every claim of the pairs with a conflict, and of all pairs claimed more than once under
+    /// `ConflictPolicy::MajorityVote`. Keyed like `provenance`.
+    claims: HashMap<[u32; 2], Vec<Claim>>,
+    /// Pairs known to combine into nothing, keyed like `provenance`.
+    nothing: HashSet<[u32; 2]>,
+    /// Metadata of the items some source described.
+    info: HashMap<u32, ItemInfo>,
+    /// Applied to the names given to the insertion methods.
+    normalization: Normalization,
+    /// Items and recipes in the file layout when read from a file, in which case `names`, `id`,
+    /// `map`, `provenance` and `nothing` are empty until the first modification.
+    frozen: Option<Frozen>,
 }
 
 impl RecipeMap {
@@ -30,30 +126,63 @@ impl RecipeMap {
             names: Vec::new(),
             id: HashMap::new(),
             map: HashMap::new(),
+            sources: Vec::new(),
+            provenance: HashMap::new(),
+            policy: ConflictPolicy::default(),
+            claims: HashMap::new(),
+            nothing: HashSet::new(),
+            info: HashMap::new(),
+            normalization: Normalization::default(),
+            frozen: None,
         }
     }
 
     #[inline]
     pub fn num_recipes(&self) -> usize {
-        self.map.len()
+        match &self.frozen {
+            Some(frozen) => frozen.arcs.len(),
+            None => self.map.len(),
+        }
     }
 
     #[inline]
     pub fn num_items(&self) -> usize {
-        self.names.len()
+        match &self.frozen {
+            Some(frozen) => frozen.num_items(),
+            None => self.names.len(),
+        }
     }
 
     #[inline]
-    pub fn items(&self) -> impl Iterator<Item = u32> + DoubleEndedIterator<Item = u32> {
+    pub fn items(&self) -> impl DoubleEndedIterator<Item = u32> {
         0..self.num_items() as u32
     }
 
     #[inline]
     pub fn get(&self, first: u32, second: u32) -> Option<u32> {
-        self.map.get(&[first, second]).copied()
+        match &self.frozen {
+            Some(frozen) => frozen.get(first, second),
+            None => self.map.get(&[first, second]).copied(),
+        }
     }
 
+    /// Inserts a recipe, resolving a disagreement with an existing recipe of the pair by the
+    /// conflict policy. Returns whether the recipe of the pair changed.
     pub fn insert(&mut self, first: &str, second: &str, result: &str) -> bool {
+        self.insert_claim(first, second, result, None)
+    }
+
+    fn insert_claim(
+        &mut self,
+        first: &str,
+        second: &str,
+        result: &str,
+        provenance: Option<Provenance>,
+    ) -> bool {
+        self.thaw();
+        let normalization = self.normalization;
+        let [first, second, result] = [first, second, result].map(|u| normalization.normalize(u));
+        let (first, second, result) = (first.as_ref(), second.as_ref(), result.as_ref());
         let (first, second) = if first > second {
             (second, first)
         } else {
@@ -61,18 +190,26 @@ impl RecipeMap {
         };
         let first = self.intern(first);
         let second = self.intern(second);
+        let key = pair_key(first, second);
+        let claim = Claim {
+            result: result.to_string(),
+            provenance,
+        };
 
-        if let Some(&existing) = self.map.get(&[first, second]) {
-            if self.name(existing) != result {
-                eprintln!(
-                    "Not adding conflicting recipe {} + {} = {} vs existing {}",
-                    self.name(first),
-                    self.name(second),
-                    self.name(existing),
-                    result,
-                );
+        if let Some(claims) = self.claims.get_mut(&key) {
+            claims.push(claim);
+            return self.resolve(key);
+        }
+        if let Some(&existing) = self.map.get(&key) {
+            if self.name(existing) == result && self.policy != ConflictPolicy::MajorityVote {
+                return false;
             }
-            return false;
+            let existing = Claim {
+                result: self.name(existing).to_string(),
+                provenance: self.provenance.get(&key).copied(),
+            };
+            self.claims.insert(key, vec![existing, claim]);
+            return self.resolve(key);
         }
 
         let result = self.intern(result);
@@ -81,17 +218,400 @@ impl RecipeMap {
         if first != second {
             self.map.insert([second, first], result);
         }
+        if self.nothing.remove(&key) {
+            self.provenance.remove(&key);
+        }
+        if let Some(provenance) = provenance {
+            self.provenance.insert(key, provenance);
+        }
+        true
+    }
+
+    /// Records that the pair combines into nothing, unless it has a recipe. Returns whether the
+    /// pair was untested.
+    pub fn insert_nothing(&mut self, first: &str, second: &str) -> bool {
+        self.insert_nothing_claim(first, second, None)
+    }
+
+    /// Records a nothing result like `insert_nothing` and where it was learned.
+    pub fn insert_nothing_from(
+        &mut self,
+        first: &str,
+        second: &str,
+        provenance: Provenance,
+    ) -> bool {
+        assert!(
+            (provenance.source as usize) < self.sources.len(),
+            "invalid source id"
+        );
+        self.insert_nothing_claim(first, second, Some(provenance))
+    }
+
+    fn insert_nothing_claim(
+        &mut self,
+        first: &str,
+        second: &str,
+        provenance: Option<Provenance>,
+    ) -> bool {
+        self.thaw();
+        let first = self.intern(&self.normalization.normalize(first));
+        let second = self.intern(&self.normalization.normalize(second));
+        if !self.is_untested(first, second) {
+            return false;
+        }
+        let key = pair_key(first, second);
+        self.nothing.insert(key);
+        if let Some(provenance) = provenance {
+            self.provenance.insert(key, provenance);
+        }
         true
     }
 
+    /// Whether the pair is known to combine into nothing. `get` returns `None` for it too.
+    #[inline]
+    pub fn is_nothing(&self, first: u32, second: u32) -> bool {
+        let key = pair_key(first, second);
+        match &self.frozen {
+            Some(frozen) => frozen.is_nothing(key),
+            None => self.nothing.contains(&key),
+        }
+    }
+
+    /// Whether no source has tried the pair, that is it has neither a recipe, a conflict nor a
+    /// known nothing result.
+    pub fn is_untested(&self, first: u32, second: u32) -> bool {
+        self.get(first, second).is_none()
+            && !self.is_nothing(first, second)
+            && !self.claims.contains_key(&pair_key(first, second))
+    }
+
+    #[inline]
+    pub fn num_nothing(&self) -> usize {
+        match &self.frozen {
+            Some(frozen) => frozen.nothing.len(),
+            None => self.nothing.len(),
+        }
+    }
+
+    /// Sets the recipe of the pair to the claim chosen by the policy. Returns whether it changed.
+    fn resolve(&mut self, key: [u32; 2]) -> bool {
+        let claims = &self.claims[&key];
+        let chosen = self.policy.choose(claims).map(|i| claims[i].clone());
+        let [first, second] = key;
+        let old = self.map.get(&key).copied();
+        match chosen {
+            Some(claim) => {
+                let result = self.intern(&claim.result);
+                self.map.insert([first, second], result);
+                self.map.insert([second, first], result);
+                match claim.provenance {
+                    Some(provenance) => self.provenance.insert(key, provenance),
+                    None => self.provenance.remove(&key),
+                };
+                self.nothing.remove(&key);
+                old != Some(result)
+            }
+            None => {
+                self.map.remove(&[first, second]);
+                self.map.remove(&[second, first]);
+                self.provenance.remove(&key);
+                old.is_some()
+            }
+        }
+    }
+
+    /// Sets the policy for disagreeing recipes and resolves the recorded conflicts again.
+    ///
+    /// Under `ConflictPolicy::MajorityVote` agreeing claims are recorded only from then on, so
+    /// set it before inserting recipes.
+    pub fn set_conflict_policy(&mut self, policy: ConflictPolicy) {
+        self.policy = policy;
+        let mut keys: Vec<_> = self.claims.keys().copied().collect();
+        keys.sort();
+        for key in keys {
+            self.resolve(key);
+        }
+    }
+
+    pub fn conflict_policy(&self) -> &ConflictPolicy {
+        &self.policy
+    }
+
+    /// Pairs given different results by the inserted recipes, ordered by the pair.
+    pub fn conflicts(&self) -> Vec<Conflict<'_>> {
+        let mut conflicts: Vec<_> = self
+            .claims
+            .iter()
+            .filter(|(_, claims)| claims.iter().any(|c| c.result != claims[0].result))
+            .map(|(&pair, claims)| Conflict {
+                pair,
+                claims,
+                result: self.get(pair[0], pair[1]),
+            })
+            .collect();
+        conflicts.sort_by_key(|conflict| conflict.pair);
+        conflicts
+    }
+
+    /// Inserts a recipe like `insert` and records where it was learned.
+    pub fn insert_from(
+        &mut self,
+        first: &str,
+        second: &str,
+        result: &str,
+        provenance: Provenance,
+    ) -> bool {
+        assert!(
+            (provenance.source as usize) < self.sources.len(),
+            "invalid source id"
+        );
+        self.insert_claim(first, second, result, Some(provenance))
+    }
+
+    /// Returns the id of the source with the given name, adding it if it does not exist.
+    pub fn add_source(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.source_id(name) {
+            return id;
+        }
+        let id = u32::try_from(self.sources.len()).expect("source id overflow");
+        self.sources.push(name.to_string());
+        id
+    }
+
+    #[inline]
+    pub fn num_sources(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub fn source_id(&self, name: &str) -> Option<u32> {
+        self.sources
+            .iter()
+            .position(|source| source == name)
+            .map(|i| i as u32)
+    }
+
+    pub fn source_name(&self, id: u32) -> &str {
+        self.sources.get(id as usize).expect("invalid source id")
+    }
+
+    /// Adds metadata of an item, merged with the metadata known already by `ItemInfo::merge`.
+    pub fn insert_item_info(&mut self, name: &str, info: ItemInfo) {
+        assert!(
+            info.source
+                .is_none_or(|source| (source as usize) < self.sources.len()),
+            "invalid source id"
+        );
+        let id = self.add_item(name);
+        self.info.entry(id).or_default().merge(info);
+    }
+
+    /// Returns the id of the item, adding it without recipes if it does not exist.
+    pub fn add_item(&mut self, name: &str) -> u32 {
+        let name = self.normalization.normalize(name);
+        match self.get_id(&name) {
+            Some(id) => id,
+            None => {
+                self.thaw();
+                self.intern(&name)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn item_info(&self, id: u32) -> Option<&ItemInfo> {
+        self.info.get(&id)
+    }
+
+    #[inline]
+    pub fn provenance(&self, first: u32, second: u32) -> Option<Provenance> {
+        let key = pair_key(first, second);
+        match &self.frozen {
+            Some(frozen) => frozen.provenance(key),
+            None => self.provenance.get(&key).copied(),
+        }
+    }
+
+    /// Removes every recipe not learned from one of `sources`, including those of unknown
+    /// provenance. Item ids are unchanged.
+    pub fn retain_sources(&mut self, sources: &[u32]) {
+        self.retain(|_, provenance| provenance.is_some_and(|p| sources.contains(&p.source)));
+    }
+
+    /// Removes the recipes for which `f` returns false, given the recipe with the smaller id
+    /// first and its provenance. Their recorded claims are removed too, so that no conflict
+    /// policy brings them back. Item ids are unchanged.
+    pub fn retain(&mut self, mut f: impl FnMut(Recipe, Option<Provenance>) -> bool) {
+        self.thaw();
+        let removed: Vec<_> = self
+            .map
+            .iter()
+            .filter(|&(&[first, second], &result)| {
+                first <= second
+                    && !f(
+                        Recipe::new(first, second, result),
+                        self.provenance.get(&[first, second]).copied(),
+                    )
+            })
+            .map(|(&pair, _)| pair)
+            .collect();
+        for [first, second] in removed {
+            self.remove(first, second);
+        }
+    }
+
+    /// Forgets everything known about the pair, leaving it untested. Returns the result it had.
+    pub fn remove(&mut self, first: u32, second: u32) -> Option<u32> {
+        self.thaw();
+        let key = pair_key(first, second);
+        self.claims.remove(&key);
+        self.provenance.remove(&key);
+        self.nothing.remove(&key);
+        self.map.remove(&[second, first]);
+        self.map.remove(&[first, second])
+    }
+
+    /// Sets the result of the pair regardless of the conflict policy, dropping the recorded
+    /// claims. Returns the result it had.
+    pub fn replace(
+        &mut self,
+        first: u32,
+        second: u32,
+        result: &str,
+        provenance: Option<Provenance>,
+    ) -> Option<u32> {
+        let old = self.remove(first, second);
+        let result = self.intern(&self.normalization.normalize(result));
+        let key = pair_key(first, second);
+        self.map.insert([first, second], result);
+        self.map.insert([second, first], result);
+        if let Some(provenance) = provenance {
+            self.provenance.insert(key, provenance);
+        }
+        old
+    }
+
+    /// Removes the recipes using or making the item, the pairs with it known to combine into
+    /// nothing, its metadata and the claims of pairs with it or of it as a result. The id stays
+    /// valid until `compact`.
+    pub fn remove_item(&mut self, id: u32) {
+        self.thaw();
+        let name = &self.names[id as usize];
+        let mut affected = Vec::new();
+        self.claims.retain(|&key, claims| {
+            // a conflict may have left the pair without a recipe for `retain` to find
+            if key.contains(&id) {
+                affected.push(key);
+                return false;
+            }
+            let len = claims.len();
+            claims.retain(|claim| &claim.result != name);
+            if claims.len() != len {
+                affected.push(key);
+            }
+            !claims.is_empty()
+        });
+        affected.sort();
+        for key in affected {
+            if self.claims.contains_key(&key) {
+                self.resolve(key);
+            } else {
+                self.remove(key[0], key[1]);
+            }
+        }
+
+        self.retain(|recipe, _| !recipe.pair.contains(&id) && recipe.result != id);
+        let provenance = &mut self.provenance;
+        self.nothing.retain(|pair| {
+            let keep = !pair.contains(&id);
+            if !keep {
+                provenance.remove(pair);
+            }
+            keep
+        });
+        self.info.remove(&id);
+    }
+
+    /// Renumbers the items in the order of their ids, dropping those in no recipe, claimed pair
+    /// or pair known to combine into nothing. Returns the new id of each old id.
+    ///
+    /// Graphs, searches and fingerprints of the map from before are invalidated.
+    pub fn compact(&mut self) -> Vec<Option<u32>> {
+        self.thaw();
+        let mut used = vec![false; self.num_items()];
+        let pairs = self.nothing.iter().chain(self.claims.keys());
+        for &[first, second] in pairs {
+            used[first as usize] = true;
+            used[second as usize] = true;
+        }
+        for (&[first, second], &result) in &self.map {
+            for u in [first, second, result] {
+                used[u as usize] = true;
+            }
+        }
+
+        let mut num_items = 0u32;
+        let new_id: Vec<_> = used
+            .iter()
+            .map(|&used| {
+                used.then(|| {
+                    num_items += 1;
+                    num_items - 1
+                })
+            })
+            .collect();
+        let id = |u: u32| new_id[u as usize].unwrap();
+        let pair = |[first, second]: [u32; 2]| [id(first), id(second)];
+
+        let names = std::mem::take(&mut self.names);
+        self.names = names
+            .into_iter()
+            .zip(&used)
+            .filter_map(|(name, &used)| used.then_some(name))
+            .collect();
+        self.id = self.names.iter().cloned().zip(0..).collect();
+        self.map = self.map.drain().map(|(k, v)| (pair(k), id(v))).collect();
+        self.provenance = self.provenance.drain().map(|(k, v)| (pair(k), v)).collect();
+        self.claims = self.claims.drain().map(|(k, v)| (pair(k), v)).collect();
+        self.nothing = self.nothing.drain().map(pair).collect();
+        self.info = self
+            .info
+            .drain()
+            .filter_map(|(u, info)| Some((new_id[u as usize]?, info)))
+            .collect();
+        new_id
+    }
+
     #[inline]
     pub fn id(&self, name: &str) -> u32 {
-        *self.id.get(name).expect("invalid item name")
+        self.get_id(name).expect("invalid item name")
     }
 
     #[inline]
     pub fn get_id(&self, name: &str) -> Option<u32> {
-        self.id.get(name).copied()
+        match &self.frozen {
+            Some(frozen) => frozen.get_id(name),
+            None => self.id.get(name).copied(),
+        }
+    }
+
+    /// Looks up a name typed by a user: the exact name if it exists, and otherwise an item whose
+    /// name is equal up to normalization and case in `index`, preferring the smallest id.
+    pub fn get_id_ignore_case(&self, name: &str, index: &NameIndex) -> Option<u32> {
+        self.get_id(name).or_else(|| index.get(name))
+    }
+
+    /// Builds the index for finding the items a misspelled name may refer to.
+    pub fn make_name_index(&self) -> NameIndex {
+        NameIndex::new(self)
+    }
+
+    /// Sets the normalization of the names inserted from then on.
+    pub fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        self.normalization
     }
 
     fn intern(&mut self, name: &str) -> u32 {
@@ -105,19 +625,132 @@ impl RecipeMap {
     }
 
     pub fn name(&self, id: u32) -> &str {
-        self.names.get(id as usize).expect("invalid item id")
+        assert!((id as usize) < self.num_items(), "invalid item id");
+        match &self.frozen {
+            Some(frozen) => frozen.name(id),
+            None => &self.names[id as usize],
+        }
     }
 
+    /// Hash of the item names and recipes, stable across runs and platforms.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(&(self.num_items() as u64).to_le_bytes());
+        for name in self.items().map(|u| self.name(u)) {
+            hash.write(&(name.len() as u64).to_le_bytes());
+            hash.write(name.as_bytes());
+        }
+        let write_recipe = |([u1, u2], u3): ([u32; 2], u32)| {
+            for u in [u1, u2, u3] {
+                hash.write(&u.to_le_bytes());
+            }
+        };
+        match &self.frozen {
+            Some(frozen) => frozen.recipes().for_each(write_recipe),
+            None => {
+                let mut pairs: Vec<_> = self.map.iter().map(|(&k, &v)| (k, v)).collect();
+                pairs.sort();
+                pairs.into_iter().for_each(write_recipe);
+            }
+        }
+        hash.0
+    }
+
+    /// Builds the graph of the recipes. The graph of a map read from a file shares its arrays.
     pub fn make_graph(&self) -> Graph {
-        Graph::from_recipe_map(self.num_items(), &self.map)
+        match &self.frozen {
+            Some(frozen) => Graph {
+                start: frozen.start.clone(),
+                arcs: frozen.arcs.clone(),
+                rev_start: frozen.rev_start.clone(),
+                rev_arcs: frozen.rev_arcs.clone(),
+            },
+            None => Graph::from_recipe_map(self.num_items(), &self.map),
+        }
     }
 
-    pub fn as_serializable(&self) -> (&[String], Vec<([u32; 2], u32)>) {
-        let pairs = self.map.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
-        (&self.names, pairs)
+    /// Converts the file layout into the modifiable representation.
+    fn thaw(&mut self) {
+        let Some(frozen) = self.frozen.take() else {
+            return;
+        };
+        self.names = (0..frozen.num_items() as u32)
+            .map(|u| frozen.name(u).to_owned())
+            .collect();
+        self.id = self.names.iter().cloned().zip(0..).collect();
+        self.map = frozen.recipes().collect();
+        self.provenance = frozen
+            .provenance
+            .iter()
+            .map(|record| (record.pair, record.provenance()))
+            .collect();
+        self.nothing = frozen.nothing.iter().copied().collect();
     }
 
-    pub fn from_serialized((names, pairs): (Vec<&str>, Vec<([u32; 2], u32)>)) -> Self {
+    /// Converts the map into the file layout.
+    fn freeze(&self) -> Frozen {
+        if let Some(frozen) = &self.frozen {
+            return frozen.clone();
+        }
+        let mut name_start = vec![0u64];
+        let mut names = Vec::new();
+        for name in &self.names {
+            names.extend_from_slice(name.as_bytes());
+            name_start.push(names.len() as u64);
+        }
+        let mut by_name: Vec<u32> = self.items().collect();
+        by_name.sort_by_key(|&u| &self.names[u as usize]);
+        let graph = self.make_graph();
+        let mut provenance: Vec<_> = self
+            .provenance
+            .iter()
+            .map(|(&pair, &provenance)| ProvenanceRecord::new(pair, provenance))
+            .collect();
+        provenance.sort_by_key(|record| record.pair);
+        let mut nothing: Vec<_> = self.nothing.iter().copied().collect();
+        nothing.sort();
+        Frozen {
+            names: names.into(),
+            name_start: name_start.into(),
+            by_name: by_name.into(),
+            start: graph.start,
+            arcs: graph.arcs,
+            rev_start: graph.rev_start,
+            rev_arcs: graph.rev_arcs,
+            provenance: provenance.into(),
+            nothing: nothing.into(),
+        }
+    }
+
+    /// Tuple of the names, recipes, sources, provenance and nothing results, the bincode layout
+    /// of the files before `to_bytes`. `from_bincode` also reads the earlier layouts without
+    /// provenance or nothing results, which nothing writes anymore.
+    pub fn to_serialized(&self) -> Serialized<'_> {
+        let names = self.items().map(|u| self.name(u)).collect();
+        let sources = self.sources.iter().map(|s| s.as_str()).collect();
+        match &self.frozen {
+            Some(frozen) => (
+                names,
+                frozen.recipes().collect(),
+                sources,
+                frozen
+                    .provenance
+                    .iter()
+                    .map(|record| (record.pair, record.provenance()))
+                    .collect(),
+                frozen.nothing.to_vec(),
+            ),
+            None => (
+                names,
+                self.map.iter().map(|(k, v)| (*k, *v)).collect(),
+                sources,
+                self.provenance.iter().map(|(k, v)| (*k, *v)).collect(),
+                self.nothing.iter().copied().collect(),
+            ),
+        }
+    }
+
+    pub fn from_serialized((names, pairs, sources, provenance, nothing): Serialized) -> Self {
         Self {
             names: names.iter().map(|&name| name.to_owned()).collect(),
             id: names
@@ -126,8 +759,166 @@ impl RecipeMap {
                 .map(|(&name, u)| (name.to_owned(), u))
                 .collect(),
             map: pairs.into_iter().collect(),
+            sources: sources.iter().map(|&name| name.to_owned()).collect(),
+            provenance: provenance.into_iter().collect(),
+            policy: ConflictPolicy::default(),
+            claims: HashMap::new(),
+            nothing: nothing.into_iter().collect(),
+            info: HashMap::new(),
+            normalization: Normalization::default(),
+            frozen: None,
         }
     }
+
+    /// Encodes the map in the versioned file format of `format`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let frozen = self.freeze();
+        let sources = encode(&self.sources);
+        let mut info: Vec<_> = self.info.iter().collect();
+        info.sort_unstable_by_key(|&(&u, _)| u);
+        let info = encode(&info);
+        let mut claims: Vec<_> = self.claims.iter().collect();
+        claims.sort_unstable_by_key(|&(&pair, _)| pair);
+        let claims = encode(&(&self.policy, &claims));
+        let mut sections = frozen.sections();
+        for (tag, payload) in [
+            (SECTION_SOURCES, &sources),
+            (SECTION_ITEM_INFO, &info),
+            (SECTION_CLAIMS, &claims),
+        ] {
+            sections.push(format::Section {
+                tag,
+                flags: 0,
+                payload,
+            });
+        }
+        format::write(&sections)
+    }
+
+    /// Opens a recipe map file, using the items and recipes in place from a memory map. Only the
+    /// section lengths are checked, so that the time does not grow with the file: the checksums
+    /// and the arrays are not, and a damaged file may make the lookups panic. `open_verified`
+    /// checks them.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        Self::open_mapped(path, false)
+    }
+
+    /// Opens a recipe map file like `open`, but verifies the checksums and the consistency of the
+    /// arrays as `from_bytes` does, reading the whole file.
+    pub fn open_verified(path: &Path) -> anyhow::Result<Self> {
+        Self::open_mapped(path, true)
+    }
+
+    fn open_mapped(path: &Path, verify: bool) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: the file must not be modified while it is mapped, as with any other reader
+        let file = Arc::new(unsafe { memmap2::Mmap::map(&file)? });
+        if !format::is_versioned(&file) {
+            return Self::from_bytes(&file);
+        }
+        let (version, sections) = if verify {
+            format::read(&file, &KNOWN_SECTIONS)?
+        } else {
+            format::read_unchecked(&file, &KNOWN_SECTIONS)?
+        };
+        if version < 2 {
+            return Self::from_bytes(&file);
+        }
+        let frozen = Frozen::from_sections(Some(&file), &sections)?;
+        Self::from_frozen(frozen, &sections, verify)
+    }
+
+    /// With `verify`, also checks the arrays of `frozen`, which takes time linear in their size.
+    fn from_frozen(
+        frozen: Frozen,
+        sections: &[format::Section],
+        verify: bool,
+    ) -> anyhow::Result<Self> {
+        let sources: Option<Vec<String>> = decode_section(sections, SECTION_SOURCES)?;
+        let info: Option<Vec<(u32, ItemInfo)>> = decode_section(sections, SECTION_ITEM_INFO)?;
+        let (policy, claims): ClaimsSection =
+            decode_section(sections, SECTION_CLAIMS)?.unwrap_or_default();
+        let frozen_items = frozen.num_items();
+        let info: HashMap<_, _> = info.into_iter().flatten().collect();
+        ensure!(
+            info.keys().all(|&u| (u as usize) < frozen_items),
+            "corrupt section \"INFO\": invalid item id"
+        );
+        let num_sources = sources.as_ref().map_or(0, |sources| sources.len());
+        if verify {
+            frozen.validate()?;
+            ensure!(
+                frozen
+                    .provenance
+                    .iter()
+                    .all(|record| (record.source as usize) < num_sources),
+                "corrupt section \"PREC\": invalid source id"
+            );
+        }
+        ensure!(
+            claims.iter().all(|(pair, claims)| {
+                pair.iter().all(|&u| (u as usize) < frozen_items)
+                    && claims.iter().all(|claim| {
+                        claim
+                            .provenance
+                            .is_none_or(|p| (p.source as usize) < num_sources)
+                    })
+            }),
+            "corrupt section \"CLMS\": invalid item or source id"
+        );
+        Ok(Self {
+            sources: sources.unwrap_or_default(),
+            policy,
+            claims: claims.into_iter().collect(),
+            info,
+            frozen: Some(frozen),
+            ..Self::new()
+        })
+    }
+
+    /// Decodes a file written by `to_bytes`, or a legacy bincode tuple of `to_serialized`.
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        if !format::is_versioned(data) {
+            return Self::from_bincode(data).context(
+                "not a recipe map file: no header and not a legacy bincode recipe map; \
+                 regenerate it with combine-recipes",
+            );
+        }
+        let (version, sections) = format::read(data, &KNOWN_SECTIONS)?;
+        if version >= 2 {
+            let frozen = Frozen::from_sections(None, &sections)?;
+            return Self::from_frozen(frozen, &sections, true);
+        }
+        let names = decode_section(&sections, SECTION_NAMES)?;
+        let pairs = decode_section(&sections, SECTION_RECIPES)?;
+        let (sources, provenance) = decode_section(&sections, SECTION_PROVENANCE)?.unzip();
+        let nothing = decode_section(&sections, SECTION_NOTHING)?;
+        match (names, pairs) {
+            (Some(names), Some(pairs)) => Ok(Self::from_serialized((
+                names,
+                pairs,
+                sources.unwrap_or_default(),
+                provenance.unwrap_or_default(),
+                nothing.unwrap_or_default(),
+            ))),
+            _ => bail!("missing item names or recipes; regenerate the file with combine-recipes"),
+        }
+    }
+
+    /// Reads a bincode file written by `to_serialized`, also accepting files written before
+    /// provenance or nothing results were recorded.
+    pub fn from_bincode(data: &[u8]) -> bincode::Result<Self> {
+        bincode::deserialize::<Serialized>(data)
+            .or_else(|_| {
+                let (names, pairs, sources, provenance) = bincode::deserialize(data)?;
+                Ok((names, pairs, sources, provenance, Vec::new()))
+            })
+            .or_else(|_: bincode::Error| {
+                let (names, pairs) = bincode::deserialize(data)?;
+                Ok((names, pairs, Vec::new(), Vec::new(), Vec::new()))
+            })
+            .map(Self::from_serialized)
+    }
 }
 
 impl Default for RecipeMap {
@@ -136,18 +927,85 @@ impl Default for RecipeMap {
     }
 }
 
+#[inline]
+fn pair_key(first: u32, second: u32) -> [u32; 2] {
+    [first.min(second), first.max(second)]
+}
+
+// sections of format version 1, encoded with bincode
+const SECTION_NAMES: [u8; 4] = *b"NAME";
+const SECTION_RECIPES: [u8; 4] = *b"RCPE";
+const SECTION_PROVENANCE: [u8; 4] = *b"PROV";
+const SECTION_NOTHING: [u8; 4] = *b"NTHG";
+
+const SECTION_SOURCES: [u8; 4] = *b"SRCS";
+const SECTION_ITEM_INFO: [u8; 4] = *b"INFO";
+/// Conflict policy and the claims of the pairs with several, so that pairs left without a
+/// recipe by a conflict stay tested.
+const SECTION_CLAIMS: [u8; 4] = *b"CLMS";
+
+type ClaimsSection = (ConflictPolicy, Vec<([u32; 2], Vec<Claim>)>);
+
+const KNOWN_SECTIONS: [[u8; 4]; 16] = [
+    SECTION_NAMES,
+    SECTION_RECIPES,
+    SECTION_PROVENANCE,
+    SECTION_NOTHING,
+    SECTION_SOURCES,
+    SECTION_ITEM_INFO,
+    SECTION_CLAIMS,
+    mapped::SECTION_NAME_BYTES,
+    mapped::SECTION_NAME_START,
+    mapped::SECTION_BY_NAME,
+    mapped::SECTION_START,
+    mapped::SECTION_ARCS,
+    mapped::SECTION_REV_START,
+    mapped::SECTION_REV_ARCS,
+    mapped::SECTION_PROVENANCE,
+    mapped::SECTION_NOTHING,
+];
+
+fn encode(value: &impl Serialize) -> Vec<u8> {
+    bincode::serialize(value).expect("in-memory serialization cannot fail")
+}
+
+fn decode_section<'a, T: Deserialize<'a>>(
+    sections: &[format::Section<'a>],
+    tag: [u8; 4],
+) -> anyhow::Result<Option<T>> {
+    let Some(section) = sections.iter().find(|section| section.tag == tag) else {
+        return Ok(None);
+    };
+    bincode::deserialize(section.payload)
+        .map(Some)
+        .with_context(|| format!("corrupt section {:?}", section.name()))
+}
+
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
 pub struct Graph {
-    start: Vec<usize>,
-    arcs: Vec<(u32, u32)>,
-    rev_start: Vec<usize>,
-    rev_arcs: Vec<[u32; 2]>,
-    map: HashMap<[u32; 2], u32>,
+    start: Array<u64>,
+    arcs: Array<[u32; 2]>,
+    rev_start: Array<u64>,
+    rev_arcs: Array<[u32; 2]>,
 }
 
 impl Graph {
     pub fn from_recipe_map(n: usize, map: &HashMap<[u32; 2], u32>) -> Self {
-        let mut start = vec![0usize; n + 1];
-        let mut rev_start = vec![0usize; n + 1];
+        let mut start = vec![0u64; n + 1];
+        let mut rev_start = vec![0u64; n + 1];
         for (&[first, second], &result) in map {
             start[first as usize] += 1;
 
@@ -160,30 +1018,65 @@ impl Graph {
             rev_start[i + 1] += rev_start[i];
         }
 
-        let mut arcs = vec![(0, 0); start[n]];
-        let mut rev_arcs = vec![[0; 2]; rev_start[n]];
+        let mut arcs = vec![[0; 2]; start[n] as usize];
+        let mut rev_arcs = vec![[0; 2]; rev_start[n] as usize];
         for (&[first, second], &result) in map {
             start[first as usize] -= 1;
-            arcs[start[first as usize]] = (second, result);
+            arcs[start[first as usize] as usize] = [second, result];
 
             if first <= second {
                 rev_start[result as usize] -= 1;
-                rev_arcs[rev_start[result as usize]] = [first, second];
+                rev_arcs[rev_start[result as usize] as usize] = [first, second];
             }
         }
 
         for i in 0..n {
-            arcs[start[i]..start[i + 1]].sort();
-            rev_arcs[rev_start[i]..rev_start[i + 1]].sort();
+            arcs[start[i] as usize..start[i + 1] as usize].sort();
+            rev_arcs[rev_start[i] as usize..rev_start[i + 1] as usize].sort();
         }
 
         Self {
-            start,
-            arcs,
-            rev_start,
-            rev_arcs,
-            map: map.clone(),
+            start: start.into(),
+            arcs: arcs.into(),
+            rev_start: rev_start.into(),
+            rev_arcs: rev_arcs.into(),
+        }
+    }
+
+    /// Adds the recipes not yet in the graph, growing it to `n` vertices, by merging them into
+    /// the sorted arc lists instead of collecting every recipe into a map as `from_recipe_map`
+    /// does. The arc arrays are still copied, so this takes time linear in the size of the
+    /// graph. Of several recipes of a pair, only the first is added. Returns the recipes actually
+    /// added.
+    pub fn add_recipes(&mut self, n: usize, recipes: &[Recipe]) -> Vec<Recipe> {
+        let mut added = Vec::new();
+        let mut new_arcs = Vec::new();
+        let mut new_rev_arcs = Vec::new();
+        let mut new_pairs = HashSet::new();
+        for recipe in recipes {
+            let [first, second] = recipe.pair;
+            let (first, second) = (first.min(second), first.max(second));
+            if (first as usize) < self.num_vertices() && self.get_result(first, second).is_some()
+                || !new_pairs.insert([first, second])
+            {
+                continue;
+            }
+            new_arcs.push((first, [second, recipe.result]));
+            if first != second {
+                new_arcs.push((second, [first, recipe.result]));
+            }
+            new_rev_arcs.push((recipe.result, [first, second]));
+            added.push(*recipe);
         }
+
+        let n = n.max(self.num_vertices());
+        let (start, arcs) = merge_arcs(&self.start, &self.arcs, n, new_arcs);
+        let (rev_start, rev_arcs) = merge_arcs(&self.rev_start, &self.rev_arcs, n, new_rev_arcs);
+        self.start = start.into();
+        self.arcs = arcs.into();
+        self.rev_start = rev_start.into();
+        self.rev_arcs = rev_arcs.into();
+        added
     }
 
     #[inline]
@@ -193,24 +1086,97 @@ impl Graph {
 
     #[inline]
     pub fn arcs_from(&self, u: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
-        let end = self.start[u as usize + 1];
-        let start = self.start[u as usize];
-        self.arcs[start..end].iter().copied()
+        self.arcs_from_slice(u).iter().map(|&[u2, u3]| (u2, u3))
+    }
+
+    #[inline]
+    fn arcs_from_slice(&self, u: u32) -> &[[u32; 2]] {
+        let end = self.start[u as usize + 1] as usize;
+        let start = self.start[u as usize] as usize;
+        &self.arcs[start..end]
     }
 
     #[inline]
     pub fn arcs_to(&self, u: u32) -> impl Iterator<Item = [u32; 2]> + '_ {
-        let end = self.rev_start[u as usize + 1];
-        let start = self.rev_start[u as usize];
+        let end = self.rev_start[u as usize + 1] as usize;
+        let start = self.rev_start[u as usize] as usize;
         self.rev_arcs[start..end].iter().copied()
     }
 
+    /// Binary searches the arcs of `first`, which are sorted by `second`.
     #[inline]
     pub fn get_result(&self, first: u32, second: u32) -> Option<u32> {
-        self.map.get(&[first, second]).copied()
+        let arcs = self.arcs_from_slice(first);
+        let i = arcs.binary_search_by_key(&second, |&[u2, _]| u2).ok()?;
+        Some(arcs[i][1])
+    }
+
+    /// Bytes of the arc arrays, counting arrays shared with a memory-mapped file.
+    pub fn heap_size(&self) -> usize {
+        self.start.as_bytes().len()
+            + self.arcs.as_bytes().len()
+            + self.rev_start.as_bytes().len()
+            + self.rev_arcs.as_bytes().len()
+    }
+
+    /// Marks the targets and every item appearing in some recipe tree of them.
+    pub fn ancestors(&self, targets: &[u32]) -> Vec<bool> {
+        let mut visited = vec![false; self.num_vertices()];
+        let mut stack = Vec::new();
+        for &u in targets {
+            if !replace(&mut visited[u as usize], true) {
+                stack.push(u);
+            }
+        }
+        while let Some(u3) = stack.pop() {
+            for [u1, u2] in self.arcs_to(u3) {
+                for u in [u1, u2] {
+                    if !replace(&mut visited[u as usize], true) {
+                        stack.push(u);
+                    }
+                }
+            }
+        }
+        visited
+    }
+
+    /// Subgraph keeping only the recipes whose inputs and result are all marked in `keep`.
+    /// Item ids are unchanged.
+    pub fn restrict(&self, keep: &[bool]) -> Self {
+        let map = (0..self.num_vertices() as u32)
+            .filter(|&u1| keep[u1 as usize])
+            .flat_map(|u1| self.arcs_from(u1).map(move |(u2, u3)| ([u1, u2], u3)))
+            .filter(|&([_, u2], u3)| keep[u2 as usize] && keep[u3 as usize])
+            .collect();
+        Self::from_recipe_map(self.num_vertices(), &map)
     }
 }
 
+fn merge_arcs<T: Copy + Ord>(
+    start: &[u64],
+    arcs: &[T],
+    n: usize,
+    mut new_arcs: Vec<(u32, T)>,
+) -> (Vec<u64>, Vec<T>) {
+    new_arcs.sort();
+    let mut new_arcs = new_arcs.into_iter().peekable();
+    let mut merged_start = Vec::with_capacity(n + 1);
+    let mut merged = Vec::with_capacity(arcs.len() + new_arcs.len());
+    merged_start.push(0);
+    for u in 0..n {
+        let begin = merged.len();
+        if u + 1 < start.len() {
+            merged.extend_from_slice(&arcs[start[u] as usize..start[u + 1] as usize]);
+        }
+        while let Some((_, arc)) = new_arcs.next_if(|&(v, _)| v as usize == u) {
+            merged.push(arc);
+        }
+        merged[begin..].sort();
+        merged_start.push(merged.len() as u64);
+    }
+    (merged_start, merged)
+}
+
 pub fn find_path(source: &[u32], set: &[u32], rm: &RecipeMap) -> Option<Vec<Recipe>> {
     let mut recipes: HashMap<u32, Option<Recipe>> = set.iter().map(|&u| (u, None)).collect();
     for (i, &u1) in source.iter().enumerate() {
@@ -246,3 +1212,521 @@ fn find_path_try_add(u1: u32, u2: u32, recipes: &mut HashMap<u32, Option<Recipe>
         }
     }
 }
+
+/// Chains of recipes from Water, Fire and Earth, with Geyser made two ways, and an Eclipse
+/// recipe of items unreachable from them.
+#[cfg(test)]
+pub(crate) fn example_recipe_map() -> RecipeMap {
+    let mut rm = RecipeMap::new();
+    rm.insert("Water", "Fire", "Steam");
+    rm.insert("Steam", "Fire", "Engine");
+    rm.insert("Engine", "Steam", "Train");
+    rm.insert("Water", "Earth", "Mud");
+    rm.insert("Steam", "Earth", "Geyser");
+    rm.insert("Mud", "Fire", "Geyser");
+    rm.insert("Moon", "Sun", "Eclipse");
+    rm
+}
+
+#[cfg(test)]
+pub(crate) fn random_recipe_map(
+    rng: &mut impl rand::Rng,
+    num_names: usize,
+    num_recipes: usize,
+) -> RecipeMap {
+    let names: Vec<String> = (0..num_names).map(|i| format!("{:02}", i)).collect();
+    let mut rm = RecipeMap::new();
+    for _ in 0..num_recipes {
+        let [first, second, result] = [(); 3].map(|_| &names[rng.gen_range(0..num_names)]);
+        rm.insert(first, second, result);
+    }
+    rm
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn provenance_round_trip() -> bincode::Result<()> {
+        let mut rm = RecipeMap::new();
+        let db = rm.add_source("infinite-craft.db");
+        let json = rm.add_source("relevant_recipes.json");
+        rm.insert_from(
+            "Water",
+            "Fire",
+            "Steam",
+            Provenance::new(db, Some(1710000000)),
+        );
+        rm.insert_from("Fire", "Earth", "Lava", Provenance::new(json, None));
+        assert!(!rm.insert_from("Water", "Fire", "Mist", Provenance::new(json, None)));
+        rm.insert("Water", "Earth", "Mud");
+
+        let rm = RecipeMap::from_bincode(&bincode::serialize(&rm.to_serialized())?)?;
+        let [water, fire, earth] = ["Water", "Fire", "Earth"].map(|u| rm.id(u));
+        assert_eq!(
+            rm.provenance(fire, water),
+            Some(Provenance::new(db, Some(1710000000)))
+        );
+        assert_eq!(
+            rm.provenance(earth, fire),
+            Some(Provenance::new(json, None))
+        );
+        assert_eq!(rm.provenance(water, earth), None);
+        assert_eq!(rm.source_name(json), "relevant_recipes.json");
+
+        let mut trusted = RecipeMap::from_bincode(&bincode::serialize(&rm.to_serialized())?)?;
+        trusted.retain_sources(&[db]);
+        assert_eq!(trusted.num_recipes(), 2);
+        assert_eq!(trusted.get(water, fire), Some(trusted.id("Steam")));
+        assert_eq!(trusted.get(fire, earth), None);
+        Ok(())
+    }
+
+    #[test]
+    fn conflict_policies_ignore_insertion_order() {
+        let claims = [
+            ("a", "Steam"),
+            ("b", "Mist"),
+            ("c", "Steam"),
+            ("d", "Cloud"),
+        ];
+        let policies = [
+            (ConflictPolicy::SourcePriority(vec![1, 0]), Some("Mist")),
+            (ConflictPolicy::SourcePriority(vec![3, 1]), Some("Cloud")),
+            (ConflictPolicy::MajorityVote, Some("Steam")),
+            (ConflictPolicy::RejectBoth, None),
+        ];
+        for (policy, expected) in policies {
+            for order in [[0, 1, 2, 3], [3, 2, 1, 0], [1, 3, 0, 2]] {
+                let mut rm = RecipeMap::new();
+                for source in ["a", "b", "c", "d"] {
+                    rm.add_source(source);
+                }
+                rm.set_conflict_policy(policy.clone());
+                rm.insert("Water", "Water", "Lake");
+                for i in order {
+                    let (source, result) = claims[i];
+                    let provenance = Provenance::new(rm.source_id(source).unwrap(), None);
+                    rm.insert_from("Water", "Fire", result, provenance);
+                }
+                let [water, fire] = ["Water", "Fire"].map(|u| rm.id(u));
+                assert_eq!(
+                    rm.get(fire, water).map(|u| rm.name(u)),
+                    expected,
+                    "{:?}",
+                    policy
+                );
+                assert_eq!(rm.get(water, fire), rm.get(fire, water));
+
+                let conflicts = rm.conflicts();
+                assert_eq!(conflicts.len(), 1);
+                assert_eq!(conflicts[0].pair, [water.min(fire), water.max(fire)]);
+                assert_eq!(conflicts[0].claims.len(), 4);
+                assert_eq!(conflicts[0].result, rm.get(water, fire));
+            }
+        }
+    }
+
+    #[test]
+    fn first_wins_keeps_first_recipe() {
+        let mut rm = RecipeMap::new();
+        assert!(rm.insert("Water", "Fire", "Steam"));
+        assert!(!rm.insert("Fire", "Water", "Mist"));
+        assert!(!rm.insert("Water", "Fire", "Steam"));
+        assert_eq!(rm.get(rm.id("Water"), rm.id("Fire")), Some(rm.id("Steam")));
+        assert_eq!(rm.conflicts().len(), 1);
+
+        rm.set_conflict_policy(ConflictPolicy::RejectBoth);
+        assert_eq!(rm.get(rm.id("Water"), rm.id("Fire")), None);
+        assert_eq!(rm.num_recipes(), 0);
+    }
+
+    #[test]
+    fn nothing_differs_from_untested() -> bincode::Result<()> {
+        let mut rm = RecipeMap::new();
+        let source = rm.add_source("game");
+        rm.insert("Water", "Fire", "Steam");
+        assert!(rm.insert_nothing_from("Water", "Earth", Provenance::new(source, Some(5))));
+        assert!(!rm.insert_nothing("Fire", "Water"));
+        rm.insert_nothing_from("Fire", "Earth", Provenance::new(source, None));
+        rm.insert("Earth", "Fire", "Lava");
+
+        let rm = RecipeMap::from_bincode(&bincode::serialize(&rm.to_serialized())?)?;
+        let [water, fire, earth] = ["Water", "Fire", "Earth"].map(|u| rm.id(u));
+        assert_eq!(rm.get(earth, water), None);
+        assert!(rm.is_nothing(earth, water));
+        assert!(!rm.is_untested(earth, water));
+        assert!(!rm.is_nothing(fire, water));
+        assert!(!rm.is_untested(water, fire));
+        assert!(!rm.is_nothing(fire, earth));
+        assert_eq!(rm.get(fire, earth), Some(rm.id("Lava")));
+        assert_eq!(
+            rm.provenance(water, earth),
+            Some(Provenance::new(source, Some(5)))
+        );
+        assert_eq!(rm.provenance(fire, earth), None);
+        assert!(rm.is_untested(water, water));
+        assert_eq!(rm.num_nothing(), 1);
+        assert!(rm.get_id("Nothing").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_names_are_normalized() {
+        let mut rm = RecipeMap::new();
+        rm.set_normalization(Normalization {
+            nfc: true,
+            trim: true,
+        });
+        rm.insert("Coffee", "Cake", "Cafe\u{301}");
+        rm.insert(" Café", "Pokémon ", "Pokécafé");
+        rm.insert_nothing("Café ", "Coffee");
+
+        let cafe = rm.id("Café");
+        assert_eq!(rm.num_items(), 5);
+        assert_eq!(rm.get(rm.id("Coffee"), rm.id("Cake")), Some(cafe));
+        assert_eq!(rm.get(cafe, rm.id("Pokémon")), Some(rm.id("Pokécafé")));
+        assert!(rm.is_nothing(cafe, rm.id("Coffee")));
+
+        assert_eq!(rm.get_id("café"), None);
+        let index = rm.make_name_index();
+        assert_eq!(rm.get_id_ignore_case(" CAFE\u{301}", &index), Some(cafe));
+        assert_eq!(
+            rm.get_id_ignore_case("pokémon", &index),
+            rm.get_id("Pokémon")
+        );
+        assert_eq!(rm.get_id_ignore_case("Pokemon", &index), None);
+
+        rm.insert("Cake", "cake", "Cake");
+        let index = rm.make_name_index();
+        assert_eq!(rm.get_id_ignore_case("cake", &index), Some(rm.id("cake")));
+        assert_eq!(rm.get_id_ignore_case("CAKE", &index), Some(rm.id("Cake")));
+    }
+
+    #[test]
+    fn versioned_file_round_trip() -> anyhow::Result<()> {
+        let mut rm = RecipeMap::new();
+        let db = rm.add_source("infinite-craft.db");
+        rm.insert_from("Water", "Fire", "Steam", Provenance::new(db, Some(7)));
+        rm.insert("Steam", "Earth", "Geyser");
+        rm.insert_nothing("Water", "Earth");
+
+        let data = rm.to_bytes();
+        assert!(data.starts_with(format::MAGIC));
+        let rm2 = RecipeMap::from_bytes(&data)?;
+        assert_eq!(rm2.fingerprint(), rm.fingerprint());
+        let [water, fire, earth] = ["Water", "Fire", "Earth"].map(|u| rm2.id(u));
+        assert_eq!(
+            rm2.provenance(water, fire),
+            Some(Provenance::new(db, Some(7)))
+        );
+        assert!(rm2.is_nothing(earth, water));
+
+        // unknown optional sections are skipped
+        let (_, mut sections) = format::read(&data, &KNOWN_SECTIONS)?;
+        sections.retain(|section| section.is_required());
+        sections.push(format::Section {
+            tag: *b"XTRA",
+            flags: 0,
+            payload: b"future",
+        });
+        let rm3 = RecipeMap::from_bytes(&format::write(&sections))?;
+        assert_eq!(rm3.fingerprint(), rm.fingerprint());
+        assert_eq!(rm3.num_sources(), 0);
+
+        sections.last_mut().unwrap().flags = format::REQUIRED;
+        let err = load_error(&format::write(&sections));
+        assert!(
+            err.to_string().contains("unknown required section"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn open_uses_file_in_place() -> anyhow::Result<()> {
+        let mut rm = RecipeMap::new();
+        let db = rm.add_source("infinite-craft.db");
+        rm.insert_from("Water", "Fire", "Steam", Provenance::new(db, Some(7)));
+        rm.insert("Steam", "Earth", "Geyser");
+        rm.insert("Water", "Water", "Lake");
+        rm.insert_nothing("Water", "Earth");
+
+        let path = std::env::temp_dir().join(format!("recipe-map-{}.bincode", std::process::id()));
+        std::fs::write(&path, rm.to_bytes())?;
+        let opened = RecipeMap::open(&path);
+        std::fs::remove_file(&path)?;
+        let mut rm2 = opened?;
+
+        let frozen = rm2.frozen.as_ref().unwrap();
+        assert!(frozen.arcs.is_mapped() && frozen.names.is_mapped());
+        assert_eq!(rm2.fingerprint(), rm.fingerprint());
+        assert_eq!(rm2.num_recipes(), rm.num_recipes());
+        for u1 in rm.items() {
+            assert_eq!(rm2.name(u1), rm.name(u1));
+            assert_eq!(rm2.get_id(rm.name(u1)), Some(u1));
+            for u2 in rm.items() {
+                assert_eq!(rm2.get(u1, u2), rm.get(u1, u2));
+                assert_eq!(rm2.is_nothing(u1, u2), rm.is_nothing(u1, u2));
+                assert_eq!(rm2.provenance(u1, u2), rm.provenance(u1, u2));
+            }
+        }
+        assert_eq!(rm2.get_id("Nothing"), None);
+
+        let (graph, graph2) = (rm.make_graph(), rm2.make_graph());
+        for u in rm.items() {
+            assert!(graph.arcs_from(u).eq(graph2.arcs_from(u)));
+            assert!(graph.arcs_to(u).eq(graph2.arcs_to(u)));
+        }
+        let [water, steam] = ["Water", "Steam"].map(|u| rm.id(u));
+        assert_eq!(graph2.get_result(steam, water), None);
+        assert_eq!(graph2.get_result(water, water), Some(rm.id("Lake")));
+
+        rm2.insert("Lake", "Fire", "Steam");
+        rm.insert("Lake", "Fire", "Steam");
+        assert!(rm2.frozen.is_none());
+        assert_eq!(rm2.fingerprint(), rm.fingerprint());
+        assert_eq!(
+            rm2.provenance(water, rm.id("Fire")),
+            Some(Provenance::new(db, Some(7)))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn item_info_is_merged_and_persisted() -> anyhow::Result<()> {
+        let mut rm = RecipeMap::new();
+        let [db, helper] = ["infinite-craft.db", "helper-recipes.db"].map(|s| rm.add_source(s));
+        rm.insert("Water", "Fire", "Steam");
+        let info = |emoji: Option<&str>, first_discovery, first_seen, source| ItemInfo {
+            emoji: emoji.map(str::to_owned),
+            first_discovery,
+            first_seen,
+            source: Some(source),
+        };
+        rm.insert_item_info("Steam", info(Some("💨"), false, Some(20), db));
+        rm.insert_item_info("Steam", info(Some("☁️"), true, Some(10), helper));
+        rm.insert_item_info("Water", info(None, false, None, db));
+        rm.insert_item_info("Water", info(Some("💧"), false, Some(5), helper));
+        rm.insert_item_info("Wind", info(Some("🌬️"), false, None, helper));
+
+        // the emoji of Steam is the first one given, while the source is that of first_seen
+        let steam = info(Some("💨"), true, Some(10), helper);
+        let water = info(Some("💧"), false, Some(5), helper);
+        let wind = ItemInfo {
+            source: None,
+            ..info(Some("🌬️"), false, None, helper)
+        };
+        let check = |rm: &RecipeMap| {
+            assert_eq!(rm.item_info(rm.id("Steam")), Some(&steam));
+            assert_eq!(rm.item_info(rm.id("Water")), Some(&water));
+            assert_eq!(rm.item_info(rm.id("Wind")), Some(&wind));
+            assert_eq!(rm.item_info(rm.id("Fire")), None);
+        };
+        check(&rm);
+        check(&RecipeMap::from_bytes(&rm.to_bytes())?);
+        Ok(())
+    }
+
+    #[test]
+    fn claims_are_persisted() -> anyhow::Result<()> {
+        let mut rm = RecipeMap::new();
+        let db = rm.add_source("infinite-craft.db");
+        rm.set_conflict_policy(ConflictPolicy::RejectBoth);
+        rm.insert_from("Water", "Fire", "Steam", Provenance::new(db, Some(1)));
+        rm.insert("Water", "Fire", "Mist");
+        rm.insert("Water", "Earth", "Mud");
+
+        let check = |rm: &RecipeMap| {
+            let [water, fire] = ["Water", "Fire"].map(|u| rm.id(u));
+            assert_eq!(rm.get(water, fire), None);
+            assert!(!rm.is_untested(water, fire));
+            assert_eq!(rm.conflict_policy(), &ConflictPolicy::RejectBoth);
+            let conflicts = rm.conflicts();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].pair, pair_key(water, fire));
+            assert_eq!(conflicts[0].claims[0].result, "Steam");
+            assert_eq!(
+                conflicts[0].claims[0].provenance,
+                Some(Provenance::new(db, Some(1)))
+            );
+            assert_eq!(rm.get(water, rm.id("Earth")), Some(rm.id("Mud")));
+        };
+        check(&rm);
+        let mut rm = RecipeMap::from_bytes(&rm.to_bytes())?;
+        check(&rm);
+
+        // the loaded policy still rejects a further disagreeing claim
+        rm.insert("Fire", "Water", "Steam");
+        assert_eq!(rm.get(rm.id("Water"), rm.id("Fire")), None);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_replace_and_compact() {
+        let mut rm = RecipeMap::new();
+        let db = rm.add_source("infinite-craft.db");
+        rm.set_conflict_policy(ConflictPolicy::MajorityVote);
+        rm.insert("Water", "Fire", "Steam");
+        rm.insert_from("Steam", "Earth", "Geyser", Provenance::new(db, None));
+        rm.insert("Earth", "Water", "Mud");
+        rm.insert("Earth", "Water", "Mud");
+        rm.insert("Earth", "Water", "Corrupt");
+        rm.insert("Fire", "Earth", "Lava");
+        rm.insert_nothing("Fire", "Fire");
+        let [water, fire, steam, earth] = ["Water", "Fire", "Steam", "Earth"].map(|u| rm.id(u));
+
+        assert_eq!(rm.remove(earth, steam), Some(rm.id("Geyser")));
+        assert!(rm.is_untested(steam, earth));
+        assert_eq!(rm.provenance(steam, earth), None);
+        assert_eq!(rm.remove(earth, steam), None);
+
+        assert_eq!(rm.replace(fire, water, "Vapor", None), Some(steam));
+        assert_eq!(rm.get(water, fire), Some(rm.id("Vapor")));
+
+        rm.remove_item(rm.id("Mud"));
+        assert_eq!(rm.get(water, earth), Some(rm.id("Corrupt")));
+        // a tie leaves the pair without a recipe, but claimed
+        rm.insert("Clay", "Fire", "Brick");
+        rm.insert("Clay", "Fire", "Pot");
+        let clay = rm.id("Clay");
+        assert_eq!(rm.get(clay, fire), None);
+        assert!(!rm.is_untested(clay, fire));
+        rm.remove_item(clay);
+        assert!(rm.is_untested(clay, fire));
+        assert!(rm.conflicts().is_empty());
+        rm.set_conflict_policy(ConflictPolicy::FirstWins);
+        assert_eq!(rm.get(water, earth), Some(rm.id("Corrupt")));
+        let lava = rm.id("Lava");
+        rm.retain(|recipe, _| recipe.result == lava);
+        assert_eq!(rm.num_recipes(), 2);
+        assert!(rm.is_nothing(fire, fire));
+
+        let new_id = rm.compact();
+        let names: Vec<_> = rm.items().map(|u| rm.name(u)).collect();
+        assert_eq!(names, ["Fire", "Earth", "Lava"]);
+        assert_eq!(new_id[water as usize], None);
+        assert_eq!(new_id[earth as usize], Some(1));
+        let [fire, earth, lava] = ["Fire", "Earth", "Lava"].map(|u| rm.id(u));
+        assert_eq!(rm.get(earth, fire), Some(lava));
+        assert!(rm.is_nothing(fire, fire));
+        let graph = rm.make_graph();
+        assert_eq!(graph.num_vertices(), 3);
+        assert_eq!(graph.get_result(fire, earth), Some(lava));
+        assert!(graph.arcs_to(lava).eq([[fire, earth]]));
+
+        let rm2 = RecipeMap::from_bytes(&rm.to_bytes()).unwrap();
+        assert_eq!(rm2.fingerprint(), rm.fingerprint());
+    }
+
+    #[test]
+    fn add_recipes_keeps_first_of_pair() {
+        let mut rm = RecipeMap::new();
+        rm.insert("Water", "Fire", "Steam");
+        let mut graph = rm.make_graph();
+        let [fire, water, steam] = ["Fire", "Water", "Steam"].map(|u| rm.id(u));
+        let recipes = [
+            Recipe::new(water, fire, 5),
+            Recipe::new(3, 3, 4),
+            Recipe::new(3, 3, 5),
+            Recipe::new(water, 3, steam),
+            Recipe::new(3, water, 4),
+        ];
+        let added = graph.add_recipes(6, &recipes);
+        let key = |r: &Recipe| (r.pair, r.result);
+        assert!(added
+            .iter()
+            .map(key)
+            .eq([&recipes[1], &recipes[3]].map(key)));
+        assert_eq!(graph.num_vertices(), 6);
+        assert_eq!(graph.get_result(fire, water), Some(steam));
+        assert!(graph.arcs_from(3).eq([(water, steam), (3, 4)]));
+        assert_eq!(graph.get_result(water, 3), Some(steam));
+        assert!(graph.arcs_to(4).eq([[3, 3]]));
+        assert_eq!(graph.arcs_to(5).count(), 0);
+    }
+
+    #[test]
+    fn corrupt_files_are_rejected() {
+        let mut rm = RecipeMap::new();
+        rm.insert("Water", "Fire", "Steam");
+        let data = rm.to_bytes();
+
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let err = load_error(&corrupt);
+        assert!(err.contains("checksum mismatch"), "{}", err);
+
+        let err = load_error(&data[..data.len() - 3]);
+        assert!(err.contains("truncated"), "{}", err);
+
+        let mut newer = data.clone();
+        newer[format::MAGIC.len()..][..4].copy_from_slice(&(format::VERSION + 1).to_le_bytes());
+        let err = load_error(&newer);
+        assert!(err.contains("newer"), "{}", err);
+
+        let err = load_error(b"garbage");
+        assert!(err.contains("not a recipe map file"), "{}", err);
+
+        // inconsistent arrays with valid checksums are rejected unless opened without checks
+        let path = std::env::temp_dir().join(format!("corrupt-{}.bincode", std::process::id()));
+        let (_, sections) = format::read(&data, &KNOWN_SECTIONS).unwrap();
+        for (tag, value) in [
+            (mapped::SECTION_ARCS, u32::MAX),
+            (mapped::SECTION_BY_NAME, 7),
+            (mapped::SECTION_NAME_BYTES, 0xff),
+        ] {
+            let section = sections.iter().find(|section| section.tag == tag).unwrap();
+            let mut payload = section.payload.to_vec();
+            payload[..4].copy_from_slice(&value.to_le_bytes());
+            let corrupt = format::write(
+                &sections
+                    .iter()
+                    .map(|&s| {
+                        if s.tag == tag {
+                            format::Section {
+                                payload: &payload,
+                                ..s
+                            }
+                        } else {
+                            s
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            let err = load_error(&corrupt);
+            assert!(err.contains("corrupt section"), "{}", err);
+            std::fs::write(&path, &corrupt).unwrap();
+            let err = format!("{:#}", RecipeMap::open_verified(&path).err().unwrap());
+            assert!(err.contains("corrupt section"), "{}", err);
+            assert!(RecipeMap::open(&path).is_ok());
+        }
+
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &corrupt).unwrap();
+        let err = format!("{:#}", RecipeMap::open_verified(&path).err().unwrap());
+        assert!(err.contains("checksum mismatch"), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_legacy_recipe_map() -> anyhow::Result<()> {
+        let names = vec!["Water", "Fire", "Steam"];
+        let pairs = vec![([0u32, 1u32], 2u32), ([1, 0], 2)];
+        let rm = RecipeMap::from_bytes(&bincode::serialize(&(names, pairs))?)?;
+        assert_eq!(rm.get(1, 0), Some(2));
+        assert_eq!(rm.provenance(0, 1), None);
+        assert_eq!(rm.num_sources(), 0);
+        Ok(())
+    }
+
+    fn load_error(data: &[u8]) -> String {
+        match RecipeMap::from_bytes(data) {
+            Ok(_) => panic!("loaded an invalid file"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+}
@@ -1,32 +1,532 @@
-use std::{error::Error, time::Instant};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
 
-use infinite_craft::{find_path, search::Search, Recipe, RecipeMap};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use infinite_craft::{
+    enumerate::MinSets,
+    exact::{ExactResult, ExactSearch},
+    export::{to_dot, to_mermaid},
+    find_path,
+    plan::{plan, Candidate, PlanWeights},
+    progress::CancelToken,
+    search::{MemoryBudget, Search},
+    solution::Solution,
+    Graph, Recipe, RecipeMap,
+};
+
+/// Infinite Craft solver
+#[derive(Parser)]
+struct Cli {
+    /// Recipe map file generated by `combine-recipes`
+    #[arg(long, global = true, default_value = "recipe-map.bincode")]
+    recipe_map: PathBuf,
+
+    /// Verify the checksums and the consistency of the whole recipe map file when opening it,
+    /// instead of using it in place unchecked
+    #[arg(long, global = true)]
+    verify: bool,
+
+    /// Use only the recipes learned from these sources of the recipe map
+    #[arg(long, global = true, value_delimiter = ',')]
+    trusted_sources: Vec<String>,
+
+    /// Use the closest item name instead of failing when a name is not found and exactly one
+    /// item is closest
+    #[arg(long, global = true)]
+    auto_correct: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print minimum-cardinality paths to the given targets
+    Solve {
+        /// Target item names
+        #[arg(required = true)]
+        targets: Vec<String>,
+
+        /// Prove the minimum cardinality with an exhaustive search after the heuristic search
+        #[arg(long)]
+        exact: bool,
+
+        /// Show the source each recipe of the paths was learned from, and when the results were
+        /// first seen
+        #[arg(long)]
+        provenance: bool,
+
+        /// How to print the paths
+        #[arg(long, value_enum, default_value_t = PathFormat::Text)]
+        format: PathFormat,
+
+        /// Enumerate every path of minimum cardinality by backtracking over the recipes, instead
+        /// of printing those of the sets kept by the search
+        #[arg(long)]
+        all: bool,
+
+        /// Stop enumerating after this many paths per target
+        #[arg(long, requires = "all")]
+        max_paths: Option<usize>,
+
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Print the number of reachable items and the sum of their cardinalities
+    Stats {
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Compare the search result against a reference file of optimal recipes
+    Compare {
+        /// Reference file in the `best_recipes_depth_*.txt` format
+        #[arg(long, default_value = "best_recipes_depth_9.txt")]
+        reference: PathBuf,
+
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Rank the untested pairs of cheap reachable items by how promising they are to try
+    Plan {
+        /// Number of pairs to print
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+
+        /// Consider only pairs among this many reached items of smallest cardinality
+        #[arg(long, default_value_t = 1000)]
+        pool: usize,
+
+        /// Write the whole ranking to this file as JSON lines of `first`, `second`, `cost` and
+        /// `score`, to be used as the queue of the crawler
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PathFormat {
+    /// One line of recipes per path
+    Text,
+    /// Graphviz DOT digraph per path
+    Dot,
+    /// Mermaid flowchart per path
+    Mermaid,
+    /// JSON array of the solutions of all targets
+    Json,
+    /// One JSON solution per line
+    Jsonl,
+}
+
+impl PathFormat {
+    /// Where the status lines go, kept off stdout when it carries diagrams or JSON.
+    fn status(self) -> Status {
+        match self {
+            Self::Text => Status::Stdout,
+            Self::Dot | Self::Mermaid | Self::Json | Self::Jsonl => Status::Stderr,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Status {
+    Stdout,
+    Stderr,
+}
+
+/// Prints a status line to the stream `Status` names.
+macro_rules! status {
+    ($status:expr, $($arg:tt)*) => {
+        match $status {
+            Status::Stdout => println!($($arg)*),
+            Status::Stderr => eprintln!($($arg)*),
+        }
+    };
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    /// Source item names
+    #[arg(long, value_delimiter = ',', default_value = "Water,Fire,Wind,Earth")]
+    source: Vec<String>,
+
+    /// Maximum cardinality of a set to search
+    #[arg(long, default_value_t = 300)]
+    max_card: usize,
+
+    /// Maximum number of sets kept per item (unlimited by default)
+    #[arg(long, default_value_t = usize::MAX, hide_default_value = true)]
+    max_count: usize,
+
+    /// Number of threads relaxing items of the same cardinality concurrently
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// Print search progress to stderr
+    #[arg(long)]
+    progress: bool,
+
+    /// Stop the search after this many seconds and keep the settled results
+    #[arg(long)]
+    timeout: Option<f64>,
+
+    /// Lower max-count during the search to keep at most this many sets in total
+    #[arg(long, conflicts_with = "max_memory")]
+    max_sets: Option<usize>,
+
+    /// Lower max-count during the search to keep the sets within this many MiB
+    #[arg(long)]
+    max_memory: Option<usize>,
+
+    /// Save the search state to this file periodically and when the search is cancelled
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = 60.0)]
+    checkpoint_interval: f64,
+
+    /// Continue the search saved in the checkpoint file instead of starting a new one
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let rm =
-        RecipeMap::from_serialized(bincode::deserialize(&std::fs::read("recipe-map.bincode")?)?);
+    let cli = Cli::parse();
+
+    let open = if cli.verify {
+        RecipeMap::open_verified
+    } else {
+        RecipeMap::open
+    };
+    let mut rm = open(&cli.recipe_map)
+        .map_err(|e| format!("cannot load {}: {:#}", cli.recipe_map.display(), e))?;
+    if !cli.trusted_sources.is_empty() {
+        let sources = cli
+            .trusted_sources
+            .iter()
+            .map(|name| source_to_id(name, &rm))
+            .collect::<Result<Vec<_>, _>>()?;
+        rm.retain_sources(&sources);
+    }
+
+    let status = match &cli.command {
+        Command::Solve { format, .. } => format.status(),
+        _ => Status::Stdout,
+    };
+    status!(
+        status,
+        "{} items, {} recipes",
+        rm.num_items(),
+        rm.num_recipes()
+    );
+
+    match &cli.command {
+        Command::Solve {
+            targets,
+            exact,
+            provenance,
+            format,
+            all,
+            max_paths,
+            search,
+        } => {
+            let targets = names_to_ids(targets, cli.auto_correct, status, &rm)?;
+            let graph = rm.make_graph();
+            let (source, search) = run_search(
+                search,
+                &rm,
+                &graph,
+                Some(&targets),
+                cli.auto_correct,
+                status,
+            )?;
+            let max_paths = max_paths.unwrap_or(usize::MAX);
+            let min_paths =
+                |target| MinSets::new(&source, &graph, target, search.min_card(target)).paths(&rm);
+            if let PathFormat::Json | PathFormat::Jsonl = format {
+                let mut solutions: Vec<_> = targets
+                    .iter()
+                    .map(|&target| {
+                        if !*all {
+                            return Solution::from_search(&search, target, &rm);
+                        }
+                        let mut paths = min_paths(target);
+                        let mut solution = Solution::from_paths(
+                            &source,
+                            target,
+                            paths.by_ref().take(max_paths).collect(),
+                            &rm,
+                        );
+                        solution.truncated = paths.next().is_some();
+                        solution.optimal = solution.card.is_some();
+                        solution
+                    })
+                    .collect();
+                if *exact {
+                    let mut exact = ExactSearch::new(&source, &graph);
+                    for (solution, &target) in solutions.iter_mut().zip(&targets) {
+                        let (_, path) = solve_exact(&mut exact, &search, &source, target, &rm);
+                        if let Some(path) = path {
+                            solution.set_optimal_path(&source, path, &rm);
+                        }
+                    }
+                }
+                print_solutions(&solutions, *format)?;
+                return Ok(());
+            }
+            for &target in &targets {
+                if *all {
+                    print_all(
+                        min_paths(target),
+                        max_paths,
+                        &source,
+                        target,
+                        *format,
+                        *provenance,
+                        &rm,
+                    );
+                } else {
+                    print_paths(&search, &source, target, *format, *provenance, &rm);
+                }
+            }
+            if *exact {
+                let mut exact = ExactSearch::new(&source, &graph);
+                for &target in &targets {
+                    print_exact(
+                        &mut exact,
+                        &search,
+                        &source,
+                        target,
+                        *format,
+                        *provenance,
+                        &rm,
+                    );
+                }
+            }
+        }
+        Command::Stats { search } => {
+            let (_, search) = run_search(
+                search,
+                &rm,
+                &rm.make_graph(),
+                None,
+                cli.auto_correct,
+                status,
+            )?;
+            print_stats(&search, &rm);
+        }
+        Command::Compare { reference, search } => {
+            let (_, search) = run_search(
+                search,
+                &rm,
+                &rm.make_graph(),
+                None,
+                cli.auto_correct,
+                status,
+            )?;
+            print_stats(&search, &rm);
+            compare_against_optimal(&search, reference, &rm)?;
+        }
+        Command::Plan {
+            limit,
+            pool,
+            output,
+            search,
+        } => {
+            let (_, search) = run_search(
+                search,
+                &rm,
+                &rm.make_graph(),
+                None,
+                cli.auto_correct,
+                status,
+            )?;
+            let candidates = plan(&rm, &search, *pool, PlanWeights::default());
+            println!("{} untested pairs", candidates.len());
+            for c in candidates.iter().take(*limit) {
+                let [first, second] = c.pair.map(|u| rm.name(u));
+                println!("{:.3} card<={} {} + {}", c.score, c.cost, first, second);
+            }
+            if let Some(path) = output {
+                write_plan(path, &candidates, &rm)
+                    .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+            }
+        }
+    }
+
+    Ok(())
+}
 
-    println!("{} items, {} recipes", rm.num_items(), rm.num_recipes());
+fn run_search(
+    args: &SearchArgs,
+    rm: &RecipeMap,
+    graph: &Graph,
+    targets: Option<&[u32]>,
+    auto_correct: bool,
+    status: Status,
+) -> Result<(Vec<u32>, Search), Box<dyn Error>> {
+    let source = names_to_ids(&args.source, auto_correct, status, rm)?;
 
-    let max_count = usize::MAX;
-    let max_card = 300;
-    println!("max_width={}, max_card={}", max_count, max_card);
+    status!(
+        status,
+        "max_width={}, max_card={}",
+        args.max_count,
+        args.max_card
+    );
+
+    // hashing every recipe is only needed to tie a checkpoint to the recipe map
+    let checkpoint = args
+        .checkpoint
+        .as_ref()
+        .map(|path| (path, rm.fingerprint()));
+    let mut search = match checkpoint {
+        Some((path, fingerprint)) if args.resume => {
+            status!(status, "Resuming from {}", path.display());
+            let search = Search::load_checkpoint(path, fingerprint)
+                .map_err(|e| format!("cannot resume from {}: {:#}", path.display(), e))?;
+            check_resume(&search, args, &source, targets, rm)
+                .map_err(|e| format!("cannot resume from {}: {}", path.display(), e))?;
+            search
+        }
+        _ => Search::new(rm.num_items(), args.max_count, args.max_card),
+    };
+    if let Some((path, fingerprint)) = checkpoint {
+        let interval = Duration::from_secs_f64(args.checkpoint_interval);
+        search.set_checkpoint(path, interval, fingerprint);
+    }
+    search.set_num_threads(args.threads);
+    if let Some(max_sets) = args.max_sets {
+        search.set_memory_budget(MemoryBudget::Sets(max_sets));
+    }
+    if let Some(max_memory) = args.max_memory {
+        search.set_memory_budget(MemoryBudget::Bytes(max_memory << 20));
+    }
+    if args.progress {
+        search.set_observer(|p| {
+            eprint!(
+                "\rcard={} settled={} queue={} sets={} {:.1}s ",
+                p.card,
+                p.num_settled,
+                p.queue_len,
+                p.num_sets,
+                p.elapsed.as_secs_f64()
+            );
+        });
+    }
+    if let Some(timeout) = args.timeout {
+        let cancel = CancelToken::new();
+        search.set_cancel_token(cancel.clone());
+        let timeout = Duration::from_secs_f64(timeout);
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            cancel.cancel();
+        });
+    }
 
-    let mut search = Search::new(rm.num_items(), max_count, max_card);
-    let graph = rm.make_graph();
+    let instant = Instant::now();
+    match targets {
+        _ if args.resume => search.resume(graph),
+        Some(targets) => search.search_to_targets(&source, targets, graph),
+        None => search.search_from_source(&source, graph),
+    }
+    if args.progress {
+        eprintln!();
+    }
+    status!(status, "Search took {}ms", instant.elapsed().as_millis());
+    if search.max_count() < args.max_count {
+        status!(
+            status,
+            "Memory budget lowered max_width to {}, {} items truncated",
+            search.max_count(),
+            search.truncated_items().count()
+        );
+    }
+    if !search.is_complete() {
+        status!(
+            status,
+            "Search cancelled, results are final only for items not marked as partial"
+        );
+    }
 
-    let source_names = ["Water", "Fire", "Wind", "Earth"];
-    let source = source_names
-        .into_iter()
-        .map(|name| name_to_id(name, &rm))
-        .collect::<Result<Vec<_>, _>>()?;
+    let source = search.source().to_vec();
+    Ok((source, search))
+}
 
+/// Checks that the checkpoint was made with the same search arguments, since resuming continues
+/// with those of the checkpoint.
+fn check_resume(
+    search: &Search,
+    args: &SearchArgs,
+    source: &[u32],
+    targets: Option<&[u32]>,
+    rm: &RecipeMap,
+) -> Result<(), String> {
+    let names = |ids: &[u32]| {
+        let mut names: Vec<_> = ids.iter().map(|&u| rm.name(u)).collect();
+        names.sort();
+        names
+    };
+    if names(search.source()) != names(source) {
+        return Err(format!(
+            "checkpoint was made from the source {:?}, not {:?}",
+            names(search.source()),
+            names(source)
+        ));
+    }
+    if search.targets().map(names) != targets.map(names) {
+        let describe = |targets: Option<&[u32]>| match targets {
+            Some(targets) => format!("the targets {:?}", names(targets)),
+            None => "all items".to_string(),
+        };
+        return Err(format!(
+            "checkpoint was made for {}, not {}",
+            describe(search.targets()),
+            describe(targets)
+        ));
+    }
+    if search.max_card() != args.max_card {
+        return Err(format!(
+            "checkpoint was made with max_card={}, not {}",
+            search.max_card(),
+            args.max_card
+        ));
+    }
+    // the memory budget of the checkpoint may have lowered max_count
+    let max_count = search.max_count();
+    if max_count > args.max_count || max_count < args.max_count && search.memory_budget().is_none()
     {
-        let instant = Instant::now();
-        search.search_from_source(&source, &graph);
-        println!("Search took {}ms", instant.elapsed().as_millis());
+        return Err(format!(
+            "checkpoint was made with max_width={}, not {}",
+            max_count, args.max_count
+        ));
+    }
+    Ok(())
+}
+
+fn write_plan(path: &Path, candidates: &[Candidate], rm: &RecipeMap) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    for c in candidates {
+        let [first, second] = c.pair.map(|u| rm.name(u));
+        let line = serde_json::json!({
+            "first": first,
+            "second": second,
+            "cost": c.cost,
+            "score": c.score,
+        });
+        writeln!(out, "{}", line)?;
     }
+    out.flush()
+}
 
+fn print_stats(search: &Search, rm: &RecipeMap) {
     let reached: Vec<_> = rm.items().filter(|&u| search.reached(u)).collect();
     println!(
         "Reached {}/{}, Sum card: {}",
@@ -34,38 +534,159 @@ fn main() -> Result<(), Box<dyn Error>> {
         rm.num_items(),
         reached.iter().map(|&u| search.min_card(u)).sum::<usize>()
     );
+}
 
-    compare_against_optimal(&search, &rm)?;
+fn print_paths(
+    search: &Search,
+    source: &[u32],
+    target: u32,
+    format: PathFormat,
+    provenance: bool,
+    rm: &RecipeMap,
+) {
+    let source_names: Vec<_> = source.iter().map(|&u| rm.name(u)).collect();
+    status!(
+        format.status(),
+        "{} paths of card={} found for {:?} from {:?}{}{}",
+        search.sets(target).len(),
+        search.min_card(target),
+        rm.name(target),
+        &source_names,
+        if search.is_settled(target) {
+            ""
+        } else {
+            " (partial)"
+        },
+        if search.is_truncated(target) {
+            " (truncated)"
+        } else {
+            ""
+        },
+    );
+    for set in search.sets(target).iter() {
+        let mut set = set.to_vec();
+        set.sort_by_key(|&u| search.min_card(u));
+        let path = find_path(source, &set, rm).unwrap();
+        print_path(&path, source, target, format, provenance, rm);
+    }
+}
 
-    // for &target_name in &["Human", "Pencil", "Joint"] {
-    for target_name in ('A'..='Z').map(|a| &*a.to_string().leak()) {
-        let target = name_to_id(target_name, &rm)?;
+/// Prints up to `max_paths` paths as they are enumerated, then their number.
+fn print_all(
+    mut paths: impl Iterator<Item = Vec<Recipe>>,
+    max_paths: usize,
+    source: &[u32],
+    target: u32,
+    format: PathFormat,
+    provenance: bool,
+    rm: &RecipeMap,
+) {
+    let mut num_paths = 0;
+    let mut card = None;
+    for path in paths.by_ref().take(max_paths) {
+        card = Some(path.len());
+        num_paths += 1;
+        print_path(&path, source, target, format, provenance, rm);
+    }
+    match card {
+        Some(card) => status!(
+            format.status(),
+            "{} minimum paths of card={} for {:?}{}",
+            num_paths,
+            card,
+            rm.name(target),
+            if paths.next().is_some() {
+                " (truncated)"
+            } else {
+                ""
+            }
+        ),
+        None => status!(format.status(), "No path found for {:?}", rm.name(target)),
+    }
+}
 
-        println!(
-            "{} paths of card={} found for {:?} from {:?}",
-            search.sets(target).len(),
-            search.min_card(target),
-            target_name,
-            &source_names,
-        );
-        for set in search.sets(target).iter() {
-            let mut set = set.to_vec();
-            set.sort_by_key(|&u| search.min_card(u));
-            let path = find_path(&source, &set, &rm).unwrap();
-            println!("- {}", format_path(&path, &rm));
+fn print_exact(
+    exact: &mut ExactSearch,
+    search: &Search,
+    source: &[u32],
+    target: u32,
+    format: PathFormat,
+    provenance: bool,
+    rm: &RecipeMap,
+) {
+    let instant = Instant::now();
+    let (result, path) = solve_exact(exact, search, source, target, rm);
+    let cert = &result.certificate;
+    match (&result.set, path) {
+        (Some(set), Some(path)) => {
+            status!(
+                format.status(),
+                "Optimal card={} for {:?}: no set of card<{} exists (level={}, {} nodes, {}ms)",
+                set.len(),
+                rm.name(target),
+                cert.min_card,
+                cert.level,
+                cert.num_nodes,
+                instant.elapsed().as_millis(),
+            );
+            print_path(&path, source, target, format, provenance, rm);
         }
+        _ => status!(format.status(), "No path found for {:?}", rm.name(target)),
     }
+}
+
+/// Runs the exact search for the target, returning a path for the optimal set if one exists.
+fn solve_exact(
+    exact: &mut ExactSearch,
+    search: &Search,
+    source: &[u32],
+    target: u32,
+    rm: &RecipeMap,
+) -> (ExactResult, Option<Vec<Recipe>>) {
+    let result = exact.solve(target, search.min_card(target));
+    let path = result.set.as_ref().map(|set| {
+        let mut set = set.clone();
+        set.sort_by_key(|&u| exact.level(u));
+        find_path(source, &set, rm).unwrap()
+    });
+    (result, path)
+}
 
+fn print_solutions(solutions: &[Solution], format: PathFormat) -> serde_json::Result<()> {
+    match format {
+        PathFormat::Json => println!("{}", serde_json::to_string_pretty(solutions)?),
+        _ => {
+            for solution in solutions {
+                println!("{}", serde_json::to_string(solution)?);
+            }
+        }
+    }
     Ok(())
 }
 
-fn format_path(path: &[Recipe], rm: &RecipeMap) -> String {
+fn print_path(
+    path: &[Recipe],
+    source: &[u32],
+    target: u32,
+    format: PathFormat,
+    provenance: bool,
+    rm: &RecipeMap,
+) {
+    match format {
+        PathFormat::Text => println!("- {}", format_path(path, provenance, rm)),
+        PathFormat::Dot => print!("{}", to_dot(path, source, target, rm)),
+        PathFormat::Mermaid => print!("{}", to_mermaid(path, source, target, rm)),
+        PathFormat::Json | PathFormat::Jsonl => unreachable!("printed by print_solutions"),
+    }
+}
+
+fn format_path(path: &[Recipe], provenance: bool, rm: &RecipeMap) -> String {
     use std::fmt::Write;
     let mut out = String::new();
     let mut prev_result = u32::MAX;
     for (i, r) in path.iter().enumerate() {
-        let pair = [rm.name(r.pair[0]), rm.name(r.pair[1])];
-        let result = rm.name(r.result);
+        let pair = r.pair.map(|u| item_label(u, rm));
+        let result = item_label(r.result, rm);
 
         if r.pair[0] == prev_result {
             write!(&mut out, " + {} -> {}", pair[1], result).unwrap();
@@ -75,13 +696,57 @@ fn format_path(path: &[Recipe], rm: &RecipeMap) -> String {
             }
             write!(&mut out, "{} + {} -> {}", pair[0], pair[1], result).unwrap();
         }
+        if provenance {
+            match rm.provenance(r.pair[0], r.pair[1]) {
+                Some(p) => {
+                    write!(&mut out, " [{}", rm.source_name(p.source)).unwrap();
+                    if let Some(timestamp) = p.timestamp {
+                        write!(&mut out, " @{}", timestamp).unwrap();
+                    }
+                    out.push(']');
+                }
+                None => out.push_str(" [unknown]"),
+            }
+            if let Some(info) = rm.item_info(r.result) {
+                let mut notes = Vec::new();
+                if info.first_discovery {
+                    notes.push("first discovery".to_string());
+                }
+                if let Some(timestamp) = info.first_seen {
+                    match info.source {
+                        Some(source) => notes.push(format!(
+                            "first seen @{} in {}",
+                            timestamp,
+                            rm.source_name(source)
+                        )),
+                        None => notes.push(format!("first seen @{}", timestamp)),
+                    }
+                }
+                if !notes.is_empty() {
+                    write!(&mut out, " ({})", notes.join(", ")).unwrap();
+                }
+            }
+        }
         prev_result = r.result;
     }
     out
 }
 
-fn compare_against_optimal(search: &Search, rm: &RecipeMap) -> Result<(), Box<dyn Error>> {
-    let text = std::fs::read_to_string("best_recipes_depth_9.txt")?;
+/// Name of the item with its emoji, if known.
+fn item_label(u: u32, rm: &RecipeMap) -> String {
+    match rm.item_info(u).and_then(|info| info.emoji.as_deref()) {
+        Some(emoji) => format!("{} {}", emoji, rm.name(u)),
+        None => rm.name(u).to_string(),
+    }
+}
+
+fn compare_against_optimal(
+    search: &Search,
+    reference: &Path,
+    rm: &RecipeMap,
+) -> Result<(), Box<dyn Error>> {
+    let text = std::fs::read_to_string(reference)
+        .map_err(|e| format!("cannot read {}: {}", reference.display(), e))?;
     let mut num_found = 0;
     let mut num_suboptimal = 0;
     let mut num_suboptimal2 = 0;
@@ -127,10 +792,59 @@ fn read_best_recipes(text: &str) -> Vec<(&str, Vec<&str>)> {
     res
 }
 
-fn name_to_id(name: &str, rm: &RecipeMap) -> Result<u32, String> {
-    if let Some(id) = rm.get_id(name) {
-        Ok(id)
-    } else {
-        Err(format!("{:?} not found", name))
+fn source_to_id(name: &str, rm: &RecipeMap) -> Result<u32, String> {
+    rm.source_id(name).ok_or_else(|| {
+        let known: Vec<_> = (0..rm.num_sources() as u32)
+            .map(|id| rm.source_name(id))
+            .collect();
+        format!("unknown source {:?}, the recipe map has {:?}", name, known)
+    })
+}
+
+fn names_to_ids(
+    names: &[String],
+    auto_correct: bool,
+    status: Status,
+    rm: &RecipeMap,
+) -> Result<Vec<u32>, String> {
+    let mut index = None;
+    let mut ids = Vec::with_capacity(names.len());
+    for name in names {
+        if let Some(id) = rm.get_id(name) {
+            ids.push(id);
+            continue;
+        }
+        let index = index.get_or_insert_with(|| rm.make_name_index());
+        if let Some(id) = rm.get_id_ignore_case(name, index) {
+            ids.push(id);
+            continue;
+        }
+        if auto_correct {
+            if let Some(id) = index.closest(name) {
+                status!(
+                    status,
+                    "Using {:?} for unknown item {:?}",
+                    rm.name(id),
+                    name
+                );
+                ids.push(id);
+                continue;
+            }
+        }
+        let suggestions: Vec<_> = index
+            .suggest(name, 5)
+            .into_iter()
+            .map(|u| format!("{:?}", rm.name(u)))
+            .collect();
+        return Err(if suggestions.is_empty() {
+            format!("unknown item {:?}", name)
+        } else {
+            format!(
+                "unknown item {:?}, did you mean {}?",
+                name,
+                suggestions.join(", ")
+            )
+        });
     }
+    Ok(ids)
 }
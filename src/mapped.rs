@@ -0,0 +1,344 @@
+//! Read-only recipe map layout used in place from a memory-mapped file.
+
+use std::{
+    mem::{align_of, size_of},
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use anyhow::ensure;
+use bytemuck::{Pod, Zeroable};
+use memmap2::Mmap;
+
+use crate::{format, Provenance};
+
+pub(crate) const SECTION_NAME_BYTES: [u8; 4] = *b"NBLB";
+pub(crate) const SECTION_NAME_START: [u8; 4] = *b"NOFS";
+pub(crate) const SECTION_BY_NAME: [u8; 4] = *b"NSRT";
+pub(crate) const SECTION_START: [u8; 4] = *b"FSTA";
+pub(crate) const SECTION_ARCS: [u8; 4] = *b"FARC";
+pub(crate) const SECTION_REV_START: [u8; 4] = *b"RSTA";
+pub(crate) const SECTION_REV_ARCS: [u8; 4] = *b"RARC";
+pub(crate) const SECTION_PROVENANCE: [u8; 4] = *b"PREC";
+pub(crate) const SECTION_NOTHING: [u8; 4] = *b"NTHS";
+
+/// Slice either owned or borrowed from a memory-mapped file. Mutable access copies a borrowed
+/// slice first.
+pub(crate) enum Array<T> {
+    Owned(Vec<T>),
+    Mapped {
+        file: Arc<Mmap>,
+        offset: usize,
+        len: usize,
+    },
+}
+
+impl<T: Pod> Array<T> {
+    /// Borrows `payload` from `file` if it is suitably aligned, and copies it otherwise.
+    pub(crate) fn from_payload(
+        file: Option<&Arc<Mmap>>,
+        section: &format::Section,
+    ) -> anyhow::Result<Self> {
+        let payload = section.payload;
+        ensure!(
+            payload.len().is_multiple_of(size_of::<T>()),
+            "corrupt section {:?}: length {} is not a multiple of {}",
+            section.name(),
+            payload.len(),
+            size_of::<T>()
+        );
+        match file {
+            Some(file) if (payload.as_ptr() as usize).is_multiple_of(align_of::<T>()) => {
+                Ok(Self::Mapped {
+                    file: file.clone(),
+                    offset: payload.as_ptr() as usize - file.as_ptr() as usize,
+                    len: payload.len() / size_of::<T>(),
+                })
+            }
+            _ => Ok(Self::Owned(bytemuck::pod_collect_to_vec(payload))),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self)
+    }
+
+    pub(crate) fn is_mapped(&self) -> bool {
+        matches!(self, Self::Mapped { .. })
+    }
+}
+
+impl<T: Pod> Deref for Array<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Owned(vec) => vec,
+            Self::Mapped { file, offset, len } => {
+                bytemuck::cast_slice(&file[*offset..*offset + *len * size_of::<T>()])
+            }
+        }
+    }
+}
+
+impl<T: Pod> DerefMut for Array<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        if self.is_mapped() {
+            *self = Self::Owned(self.to_vec());
+        }
+        match self {
+            Self::Owned(vec) => vec,
+            Self::Mapped { .. } => unreachable!(),
+        }
+    }
+}
+
+impl<T: Clone> Clone for Array<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Owned(vec) => Self::Owned(vec.clone()),
+            Self::Mapped { file, offset, len } => Self::Mapped {
+                file: file.clone(),
+                offset: *offset,
+                len: *len,
+            },
+        }
+    }
+}
+
+impl<T> Default for Array<T> {
+    fn default() -> Self {
+        Self::Owned(Vec::new())
+    }
+}
+
+impl<T> From<Vec<T>> for Array<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Self::Owned(vec)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct ProvenanceRecord {
+    pub pair: [u32; 2],
+    pub source: u32,
+    pub has_timestamp: u32,
+    pub timestamp: u64,
+}
+
+impl ProvenanceRecord {
+    pub(crate) fn new(pair: [u32; 2], provenance: Provenance) -> Self {
+        Self {
+            pair,
+            source: provenance.source,
+            has_timestamp: provenance.timestamp.is_some() as u32,
+            timestamp: provenance.timestamp.unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn provenance(&self) -> Provenance {
+        Provenance::new(
+            self.source,
+            (self.has_timestamp != 0).then_some(self.timestamp),
+        )
+    }
+}
+
+/// Recipe map in the layout of the file: the names concatenated in one blob, the recipes as the
+/// arrays of `Graph` and the other per-pair data sorted by the pair.
+#[derive(Clone, Default)]
+pub(crate) struct Frozen {
+    pub names: Array<u8>,
+    pub name_start: Array<u64>,
+    /// Item ids ordered by name.
+    pub by_name: Array<u32>,
+    pub start: Array<u64>,
+    pub arcs: Array<[u32; 2]>,
+    pub rev_start: Array<u64>,
+    pub rev_arcs: Array<[u32; 2]>,
+    pub provenance: Array<ProvenanceRecord>,
+    pub nothing: Array<[u32; 2]>,
+}
+
+impl Frozen {
+    pub(crate) fn from_sections(
+        file: Option<&Arc<Mmap>>,
+        sections: &[format::Section],
+    ) -> anyhow::Result<Self> {
+        fn array<T: Pod>(
+            file: Option<&Arc<Mmap>>,
+            sections: &[format::Section],
+            tag: [u8; 4],
+        ) -> anyhow::Result<Array<T>> {
+            match sections.iter().find(|section| section.tag == tag) {
+                Some(section) => Array::from_payload(file, section),
+                None => Ok(Array::default()),
+            }
+        }
+        let frozen = Self {
+            names: array(file, sections, SECTION_NAME_BYTES)?,
+            name_start: array(file, sections, SECTION_NAME_START)?,
+            by_name: array(file, sections, SECTION_BY_NAME)?,
+            start: array(file, sections, SECTION_START)?,
+            arcs: array(file, sections, SECTION_ARCS)?,
+            rev_start: array(file, sections, SECTION_REV_START)?,
+            rev_arcs: array(file, sections, SECTION_REV_ARCS)?,
+            provenance: array(file, sections, SECTION_PROVENANCE)?,
+            nothing: array(file, sections, SECTION_NOTHING)?,
+        };
+        let n = frozen.name_start.len().saturating_sub(1);
+        ensure!(
+            frozen.name_start.last().copied() == Some(frozen.names.len() as u64)
+                && frozen.by_name.len() == n
+                && frozen.start.len() == n + 1
+                && frozen.rev_start.len() == n + 1
+                && frozen.start.last().copied() == Some(frozen.arcs.len() as u64)
+                && frozen.rev_start.last().copied() == Some(frozen.rev_arcs.len() as u64),
+            "corrupt recipe map: inconsistent section lengths; regenerate it with combine-recipes"
+        );
+        Ok(frozen)
+    }
+
+    /// Checks the invariants the lookups rely on, so that a damaged file fails to load instead of
+    /// panicking later. It reads every array, so opening a file in place skips it by default.
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        let n = self.num_items();
+        let corrupt = |tag: [u8; 4], what: &str| {
+            format!(
+                "corrupt section {:?}: {}; regenerate it with combine-recipes",
+                String::from_utf8_lossy(&tag),
+                what
+            )
+        };
+        let is_offsets = |start: &[u64]| start[0] == 0 && start.windows(2).all(|w| w[0] <= w[1]);
+        ensure!(
+            is_offsets(&self.name_start),
+            corrupt(SECTION_NAME_START, "offsets are not increasing")
+        );
+        ensure!(
+            (0..n as u32).all(|u| std::str::from_utf8(self.name_bytes(u)).is_ok()),
+            corrupt(SECTION_NAME_BYTES, "a name is not UTF-8")
+        );
+        ensure!(
+            self.by_name.iter().all(|&u| (u as usize) < n)
+                && self
+                    .by_name
+                    .windows(2)
+                    .all(|w| self.name_bytes(w[0]) < self.name_bytes(w[1])),
+            corrupt(SECTION_BY_NAME, "ids are invalid or not sorted by name")
+        );
+        for (tag, start) in [
+            (SECTION_START, &self.start),
+            (SECTION_REV_START, &self.rev_start),
+        ] {
+            ensure!(
+                is_offsets(start),
+                corrupt(tag, "offsets are not increasing")
+            );
+        }
+        ensure!(
+            (0..n as u32).all(|u| {
+                let arcs = self.arcs_from(u);
+                arcs.iter()
+                    .all(|&[u2, u3]| (u2 as usize) < n && (u3 as usize) < n)
+                    && arcs.windows(2).all(|w| w[0][0] < w[1][0])
+            }),
+            corrupt(SECTION_ARCS, "ids are invalid or not sorted")
+        );
+        ensure!(
+            self.rev_arcs.iter().flatten().all(|&u| (u as usize) < n),
+            corrupt(SECTION_REV_ARCS, "invalid item id")
+        );
+        ensure!(
+            self.provenance
+                .iter()
+                .all(|record| record.pair.iter().all(|&u| (u as usize) < n))
+                && self.provenance.windows(2).all(|w| w[0].pair < w[1].pair),
+            corrupt(SECTION_PROVENANCE, "pairs are invalid or not sorted")
+        );
+        ensure!(
+            self.nothing.iter().flatten().all(|&u| (u as usize) < n)
+                && self.nothing.windows(2).all(|w| w[0] < w[1]),
+            corrupt(SECTION_NOTHING, "pairs are invalid or not sorted")
+        );
+        Ok(())
+    }
+
+    pub(crate) fn sections(&self) -> Vec<format::Section<'_>> {
+        let section = |tag, flags, payload| format::Section {
+            tag,
+            flags,
+            payload,
+        };
+        let required = format::REQUIRED;
+        vec![
+            section(SECTION_NAME_BYTES, required, self.names.as_bytes()),
+            section(SECTION_NAME_START, required, self.name_start.as_bytes()),
+            section(SECTION_BY_NAME, required, self.by_name.as_bytes()),
+            section(SECTION_START, required, self.start.as_bytes()),
+            section(SECTION_ARCS, required, self.arcs.as_bytes()),
+            section(SECTION_REV_START, required, self.rev_start.as_bytes()),
+            section(SECTION_REV_ARCS, required, self.rev_arcs.as_bytes()),
+            section(SECTION_PROVENANCE, 0, self.provenance.as_bytes()),
+            section(SECTION_NOTHING, 0, self.nothing.as_bytes()),
+        ]
+    }
+
+    #[inline]
+    pub(crate) fn num_items(&self) -> usize {
+        self.name_start.len() - 1
+    }
+
+    pub(crate) fn name(&self, u: u32) -> &str {
+        std::str::from_utf8(self.name_bytes(u)).expect("corrupt item name")
+    }
+
+    fn name_bytes(&self, u: u32) -> &[u8] {
+        let begin = self.name_start[u as usize] as usize;
+        let end = self.name_start[u as usize + 1] as usize;
+        &self.names[begin..end]
+    }
+
+    pub(crate) fn get_id(&self, name: &str) -> Option<u32> {
+        let i = self
+            .by_name
+            .binary_search_by(|&u| self.name(u).cmp(name))
+            .ok()?;
+        Some(self.by_name[i])
+    }
+
+    #[inline]
+    pub(crate) fn arcs_from(&self, u: u32) -> &[[u32; 2]] {
+        &self.arcs[self.start[u as usize] as usize..self.start[u as usize + 1] as usize]
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, first: u32, second: u32) -> Option<u32> {
+        let arcs = self.arcs_from(first);
+        let i = arcs.binary_search_by_key(&second, |&[u2, _]| u2).ok()?;
+        Some(arcs[i][1])
+    }
+
+    pub(crate) fn provenance(&self, key: [u32; 2]) -> Option<Provenance> {
+        let i = self
+            .provenance
+            .binary_search_by_key(&key, |record| record.pair)
+            .ok()?;
+        Some(self.provenance[i].provenance())
+    }
+
+    pub(crate) fn is_nothing(&self, key: [u32; 2]) -> bool {
+        self.nothing.binary_search(&key).is_ok()
+    }
+
+    /// Every recipe with both orders of the pair, ordered by the pair.
+    pub(crate) fn recipes(&self) -> impl Iterator<Item = ([u32; 2], u32)> + '_ {
+        (0..self.num_items() as u32).flat_map(move |u1| {
+            self.arcs_from(u1)
+                .iter()
+                .map(move |&[u2, u3]| ([u1, u2], u3))
+        })
+    }
+}
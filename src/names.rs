@@ -0,0 +1,207 @@
+//! Normalization of item names and lookup of misspelled ones.
+
+use std::{borrow::Cow, mem::replace};
+
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+use crate::RecipeMap;
+
+/// How `RecipeMap` normalizes the item names it is given. The case is kept, so the display name
+/// is the name as the source spelled it; `fold_case` compares names ignoring it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// Compose the characters into Unicode normalization form C, so that canonically equivalent
+    /// spellings such as "Café" with a precomposed or a combining accent are one item.
+    pub nfc: bool,
+    /// Remove leading and trailing whitespace.
+    pub trim: bool,
+}
+
+impl Normalization {
+    pub fn normalize(self, name: &str) -> Cow<'_, str> {
+        let name = if self.trim { name.trim() } else { name };
+        if self.nfc && !is_nfc(name) {
+            Cow::Owned(name.nfc().collect())
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
+}
+
+/// Key under which names equal up to normalization and case are equal.
+pub fn fold_case(name: &str) -> String {
+    name.trim()
+        .chars()
+        .flat_map(char::to_lowercase)
+        .nfc()
+        .collect()
+}
+
+/// Item names ordered by `fold_case`, for finding the items a user may have meant.
+pub struct NameIndex {
+    /// Folded name and item id, sorted.
+    keys: Vec<(String, u32)>,
+}
+
+impl NameIndex {
+    pub fn new(rm: &RecipeMap) -> Self {
+        let mut keys: Vec<_> = rm.items().map(|u| (fold_case(rm.name(u)), u)).collect();
+        keys.sort_unstable();
+        Self { keys }
+    }
+
+    /// The item whose name is equal to `name` up to normalization and case, preferring the
+    /// smallest id.
+    pub fn get(&self, name: &str) -> Option<u32> {
+        let key = fold_case(name);
+        let i = self
+            .keys
+            .partition_point(|(k, _)| k.as_str() < key.as_str());
+        let (k, u) = self.keys.get(i)?;
+        (*k == key).then_some(*u)
+    }
+
+    /// Items whose name starts with `prefix` ignoring case, ordered by name.
+    pub fn with_prefix(&self, prefix: &str) -> impl Iterator<Item = u32> + '_ {
+        let prefix = fold_case(prefix);
+        let begin = self
+            .keys
+            .partition_point(|(key, _)| key.as_str() < prefix.as_str());
+        self.keys[begin..]
+            .iter()
+            .take_while(move |(key, _)| key.starts_with(&prefix))
+            .map(|&(_, u)| u)
+    }
+
+    /// Items whose name contains `part` ignoring case, ordered by name.
+    pub fn containing(&self, part: &str) -> impl Iterator<Item = u32> + '_ {
+        let part = fold_case(part);
+        self.keys
+            .iter()
+            .filter(move |(key, _)| key.contains(&part))
+            .map(|&(_, u)| u)
+    }
+
+    /// Items whose name is within `max_distance` edits of `name` ignoring case, ordered by the
+    /// distance and then by id.
+    pub fn within_distance(&self, name: &str, max_distance: usize) -> Vec<(usize, u32)> {
+        let name: Vec<char> = fold_case(name).chars().collect();
+        let mut res: Vec<_> = self
+            .keys
+            .iter()
+            .filter_map(|(key, u)| {
+                let d = edit_distance(&name, key, max_distance)?;
+                Some((d, *u))
+            })
+            .collect();
+        res.sort_unstable();
+        res
+    }
+
+    /// Up to `limit` candidates for a misspelled name: the close ones by edit distance first,
+    /// then the ones starting with and the ones containing it.
+    pub fn suggest(&self, name: &str, limit: usize) -> Vec<u32> {
+        let close = self.within_distance(name, max_distance(name));
+        let mut res = Vec::new();
+        let candidates = close
+            .into_iter()
+            .map(|(_, u)| u)
+            .chain(self.with_prefix(name))
+            .chain(self.containing(name));
+        for u in candidates {
+            if res.len() == limit {
+                break;
+            }
+            if !res.contains(&u) {
+                res.push(u);
+            }
+        }
+        res
+    }
+
+    /// The item closest to `name` by edit distance, if it is close and no other item is as close.
+    pub fn closest(&self, name: &str) -> Option<u32> {
+        match self.within_distance(name, max_distance(name))[..] {
+            [(_, u)] => Some(u),
+            [(d0, u), (d1, _), ..] if d0 < d1 => Some(u),
+            _ => None,
+        }
+    }
+}
+
+/// Edits tolerated in a misspelling of `name`: one per four characters, and at least one.
+fn max_distance(name: &str) -> usize {
+    name.chars().count() / 4 + 1
+}
+
+/// Levenshtein distance between the characters of `a` and `b`, or `None` if it exceeds `max`.
+fn edit_distance(a: &[char], b: &str, max: usize) -> Option<usize> {
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut diagonal = replace(&mut row[0], i + 1);
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+        if *row.iter().min().unwrap() > max {
+            return None;
+        }
+    }
+    Some(row[b.len()]).filter(|&d| d <= max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edit_distance_matches_definition() {
+        let distance = |a: &str, b: &str| {
+            let a: Vec<char> = a.chars().collect();
+            edit_distance(&a, b, usize::MAX)
+        };
+        assert_eq!(distance("kitten", "sitting"), Some(3));
+        assert_eq!(distance("", "abc"), Some(3));
+        assert_eq!(distance("café", "cafe"), Some(1));
+        assert_eq!(distance("fire", "fire"), Some(0));
+        assert_eq!(edit_distance(&['a'; 5], "bbbbb", 4), None);
+        assert_eq!(edit_distance(&['a'], "abcd", 2), None);
+    }
+
+    #[test]
+    fn name_index_suggests_items() {
+        let mut rm = RecipeMap::new();
+        rm.insert("Water", "Fire", "Steam");
+        rm.insert("Fire", "Fire", "Volcano");
+        rm.insert("Fire", "Water", "Steam");
+        rm.insert("Steam", "Earth", "Steam Engine");
+        rm.insert("Fire", "Earth", "Firefly");
+        let index = rm.make_name_index();
+        let names = |ids: Vec<u32>| ids.into_iter().map(|u| rm.name(u)).collect::<Vec<_>>();
+
+        assert_eq!(
+            names(index.with_prefix("fir").collect()),
+            ["Fire", "Firefly"]
+        );
+        assert_eq!(
+            names(index.containing("EAM").collect()),
+            ["Steam", "Steam Engine"]
+        );
+        assert_eq!(names(index.suggest("Fier", 5)), ["Fire"]);
+        assert_eq!(names(index.suggest("stea", 5)), ["Steam", "Steam Engine"]);
+        assert_eq!(index.closest("Watr"), Some(rm.id("Water")));
+        assert_eq!(index.closest("Stem"), Some(rm.id("Steam")));
+        assert_eq!(index.closest("Xyz"), None);
+
+        rm.insert("Water", "Earth", "Fira");
+        let index = rm.make_name_index();
+        assert!(rm.get_id("Fira").is_some());
+        assert_eq!(index.closest("Firz"), None);
+        assert_eq!(index.closest("Fyra"), Some(rm.id("Fira")));
+    }
+}
@@ -0,0 +1,139 @@
+//! Ranking of the untested pairs worth trying next.
+
+use crate::{search::Search, uniform_family::ItemSet, RecipeMap};
+
+/// Sets of each family compared when estimating the cost of a pair.
+const SETS_PER_ITEM: usize = 4;
+
+/// Weights of the terms of `Candidate::score`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanWeights {
+    /// Weight of `1 / cost`, favoring pairs whose result would be cheap to make.
+    pub cheapness: f64,
+    /// Weight of the mean fraction of untested pairs of the inputs within the pool. Items tried
+    /// with few others are more likely to make something new.
+    pub novelty: f64,
+    /// Weight of the fraction of inputs with a cardinality of at most `low_card_threshold`.
+    pub low_card: f64,
+    /// Cardinality up to which an input counts as low-card.
+    pub low_card_threshold: usize,
+}
+
+impl Default for PlanWeights {
+    fn default() -> Self {
+        Self {
+            cheapness: 1.0,
+            novelty: 0.5,
+            low_card: 0.5,
+            low_card_threshold: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    /// Untested pair with the smaller id first.
+    pub pair: [u32; 2],
+    /// Cardinality the result would have if it were a new item: the size of the union of a
+    /// minimum set of each input, plus one. An upper bound, since only a few sets of each input
+    /// are compared.
+    pub cost: usize,
+    pub score: f64,
+}
+
+/// Ranks the untested pairs among the `pool_size` reached items of smallest cardinality, best
+/// first. Ties are ordered by the pair, so the plan is deterministic.
+pub fn plan<S: ItemSet>(
+    rm: &RecipeMap,
+    search: &Search<S>,
+    pool_size: usize,
+    weights: PlanWeights,
+) -> Vec<Candidate> {
+    let mut pool: Vec<u32> = rm.items().filter(|&u| search.reached(u)).collect();
+    pool.sort_by_key(|&u| (search.min_card(u), u));
+    pool.truncate(pool_size);
+    pool.sort();
+
+    let untested: Vec<Vec<bool>> = pool
+        .iter()
+        .map(|&u1| pool.iter().map(|&u2| rm.is_untested(u1, u2)).collect())
+        .collect();
+    let novelty: Vec<f64> = untested
+        .iter()
+        .map(|row| row.iter().filter(|&&b| b).count() as f64 / pool.len() as f64)
+        .collect();
+
+    let mut candidates = Vec::new();
+    for (i, &u1) in pool.iter().enumerate() {
+        for (j, &u2) in pool.iter().enumerate().skip(i) {
+            if !untested[i][j] {
+                continue;
+            }
+            let cost = union_card(search, u1, u2) + 1;
+            let low_card = [u1, u2]
+                .iter()
+                .filter(|&&u| search.min_card(u) <= weights.low_card_threshold)
+                .count() as f64
+                / 2.0;
+            let score = weights.cheapness / cost as f64
+                + weights.novelty * (novelty[i] + novelty[j]) / 2.0
+                + weights.low_card * low_card;
+            candidates.push(Candidate {
+                pair: [u1, u2],
+                cost,
+                score,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.pair.cmp(&b.pair)));
+    candidates
+}
+
+fn union_card<S: ItemSet>(search: &Search<S>, u1: u32, u2: u32) -> usize {
+    let sets = |u| search.sets(u).iter().take(SETS_PER_ITEM);
+    sets(u1)
+        .flat_map(|s1| sets(u2).map(move |s2| s1.union_len(s2)))
+        .min()
+        .unwrap_or(usize::MAX - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::example_recipe_map;
+
+    use super::*;
+
+    #[test]
+    fn plan_ranks_untested_pairs() {
+        let mut rm = example_recipe_map();
+        rm.insert_nothing("Water", "Water");
+        let graph = rm.make_graph();
+        let source = ["Water", "Fire"].map(|u| rm.id(u));
+        let mut search = Search::new(rm.num_items(), usize::MAX, 10);
+        search.search_from_source(&source, &graph);
+
+        let candidates = plan(&rm, &search, 100, PlanWeights::default());
+        let reached = ["Water", "Fire", "Steam", "Engine", "Train"].map(|u| rm.id(u));
+        let num_untested = (0..reached.len())
+            .flat_map(|i| (i..reached.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| rm.is_untested(reached[i], reached[j]))
+            .count();
+        assert_eq!(candidates.len(), num_untested);
+        for c in &candidates {
+            assert!(c.pair[0] <= c.pair[1]);
+            assert!(c.pair.iter().all(|u| reached.contains(u)));
+            assert!(rm.is_untested(c.pair[0], c.pair[1]));
+        }
+        assert!(candidates.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let [water, fire, steam, train] = ["Water", "Fire", "Steam", "Train"].map(|u| rm.id(u));
+        assert_eq!(candidates[0].pair, [fire, fire]);
+        assert_eq!(candidates[0].cost, 1);
+        let cost = |pair| candidates.iter().find(|c| c.pair == pair).unwrap().cost;
+        assert_eq!(cost([water, steam]), 2);
+        assert_eq!(cost([water, train]), 4);
+
+        let pool = plan(&rm, &search, 3, PlanWeights::default());
+        assert!(pool.iter().all(|c| c.pair.iter().all(|&u| u != train)));
+    }
+}
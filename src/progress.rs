@@ -0,0 +1,39 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Snapshot of a running `Search` passed to its observer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Cardinality of the items being relaxed. Items up to this cardinality are final.
+    pub card: usize,
+    pub num_settled: usize,
+    pub queue_len: usize,
+    pub num_sets: usize,
+    pub elapsed: Duration,
+}
+
+pub type Observer = Box<dyn FnMut(&Progress) + Send + Sync>;
+
+/// Shared flag to stop a running `Search` from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
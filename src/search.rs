@@ -2,36 +2,189 @@ use std::{
     cmp::Reverse,
     collections::BinaryHeap,
     mem::{replace, take},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::{uniform_family::UniformFamily, Graph};
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
 
-pub struct Search {
+use crate::{
+    progress::{CancelToken, Observer, Progress},
+    uniform_family::{Candidates, ItemSet, UniformFamily},
+    Graph, Recipe,
+};
+
+const REPORT_INTERVAL: usize = 1 << 12;
+
+/// Limit on the memory used by the families of a `Search`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryBudget {
+    Sets(usize),
+    Bytes(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "S: ItemSet")]
+pub struct Search<S = Vec<u32>> {
     max_card: usize,
     max_count: usize,
+    num_threads: usize,
     queue: BinaryHeap<(Reverse<usize>, u32)>,
     in_que: Vec<usize>,
-    sets: Vec<UniformFamily>,
+    sets: Vec<UniformFamily<S>>,
+    card: usize,
+    num_settled: usize,
+    num_sets: usize,
+    num_bytes: usize,
+    budget: Option<MemoryBudget>,
+    truncated: Vec<bool>,
+    source: Vec<u32>,
+    targets: Option<Vec<u32>>,
+    #[serde(skip)]
+    observer: Option<Observer>,
+    #[serde(skip)]
+    cancel: Option<CancelToken>,
+    #[serde(skip)]
+    checkpoint: Option<CheckpointConfig>,
+}
+
+struct CheckpointConfig {
+    path: PathBuf,
+    interval: Duration,
+    fingerprint: u64,
+    last: Instant,
 }
 
 impl Search {
     pub fn new(num_items: usize, max_count: usize, max_card: usize) -> Self {
+        Self::with_item_set(num_items, max_count, max_card)
+    }
+}
+
+impl<S: ItemSet> Search<S> {
+    pub fn with_item_set(num_items: usize, max_count: usize, max_card: usize) -> Self {
         Self {
             max_card,
             max_count,
+            num_threads: 1,
             queue: BinaryHeap::new(),
             in_que: vec![usize::MAX; num_items],
-            sets: vec![UniformFamily::new(); num_items],
+            sets: vec![UniformFamily::default(); num_items],
+            card: 0,
+            num_settled: 0,
+            num_sets: 0,
+            num_bytes: 0,
+            budget: None,
+            truncated: vec![false; num_items],
+            source: Vec::new(),
+            targets: None,
+            observer: None,
+            cancel: None,
+            checkpoint: None,
         }
     }
 
+    /// Number of threads relaxing the items of the same cardinality concurrently. The result
+    /// is identical to the single-threaded search.
+    pub fn set_num_threads(&mut self, num_threads: usize) {
+        self.num_threads = num_threads.max(1);
+    }
+
+    /// Called when the search moves to the next cardinality, periodically while relaxing
+    /// items of the same cardinality and when the search stops.
+    pub fn set_observer(&mut self, observer: impl FnMut(&Progress) + Send + Sync + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    /// Stops the search before relaxing the next item once `cancel` is cancelled.
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = Some(cancel);
+    }
+
+    /// Saves the search state to `path` every `interval` and when the search is cancelled.
+    /// `fingerprint` is `RecipeMap::fingerprint` of the recipe map the graph is made from.
+    pub fn set_checkpoint(
+        &mut self,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+        fingerprint: u64,
+    ) {
+        self.checkpoint = Some(CheckpointConfig {
+            path: path.into(),
+            interval,
+            fingerprint,
+            last: Instant::now(),
+        });
+    }
+
+    /// Whether the search ran to the end instead of being cancelled.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Whether the family of `u` is final. After a cancelled search, this holds for the items
+    /// whose cardinality does not exceed that of the last relaxed item.
+    #[inline]
+    pub fn is_settled(&self, u: u32) -> bool {
+        self.is_complete() || self.min_card(u) <= self.card
+    }
+
+    /// Once the families exceed `budget`, `max_count` is halved until they fit or it is one,
+    /// keeping the lexicographically smallest sets. The budget is checked after every merge, truncating only
+    /// the families of unsettled items, and again when the search moves to the next cardinality,
+    /// truncating every family.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.budget = Some(budget);
+    }
+
+    #[inline]
+    pub fn num_sets(&self) -> usize {
+        self.num_sets
+    }
+
+    #[inline]
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    #[inline]
+    pub fn max_card(&self) -> usize {
+        self.max_card
+    }
+
+    #[inline]
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.budget
+    }
+
+    /// Current limit on the number of sets per item, lowered by the memory budget.
+    #[inline]
+    pub fn max_count(&self) -> usize {
+        self.max_count
+    }
+
+    /// Whether the family of `u` may be missing sets, or even have a larger cardinality,
+    /// because it or a family it was merged from was limited by `max_count`.
+    #[inline]
+    pub fn is_truncated(&self, u: u32) -> bool {
+        self.truncated[u as usize] || self.max_count <= self.sets(u).len()
+    }
+
+    pub fn truncated_items(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.sets.len() as u32).filter(|&u| self.is_truncated(u))
+    }
+
     #[inline]
     pub fn reached(&self, u: u32) -> bool {
         !self.sets[u as usize].is_empty()
     }
 
     #[inline]
-    pub fn sets(&self, u: u32) -> &UniformFamily {
+    pub fn sets(&self, u: u32) -> &UniformFamily<S> {
         &self.sets[u as usize]
     }
 
@@ -40,33 +193,413 @@ impl Search {
         self.sets(u).card()
     }
 
+    #[inline]
+    pub fn source(&self) -> &[u32] {
+        &self.source
+    }
+
+    #[inline]
+    pub fn targets(&self) -> Option<&[u32]> {
+        self.targets.as_deref()
+    }
+
     pub fn search_from_source(&mut self, source: &[u32], graph: &Graph) {
+        self.source = source.to_vec();
         for &u in source {
+            let old = usage(&self.sets[u as usize]);
             self.sets[u as usize].set_single_empty();
+            self.replace_usage(old, usage(&self.sets[u as usize]));
             self.push(u, 0);
         }
         self.search(graph);
     }
 
-    fn search(&mut self, graph: &Graph) {
+    /// Searches only the part of `graph` that can contribute to the targets. Results for the
+    /// targets and their ancestors are the same as `search_from_source` on the whole graph.
+    pub fn search_to_targets(&mut self, source: &[u32], targets: &[u32], graph: &Graph) {
+        self.targets = Some(targets.to_vec());
+        let graph = graph.restrict(&graph.ancestors(targets));
+        self.search_from_source(source, &graph);
+    }
+
+    /// Continues a search loaded by `load_checkpoint` on the graph of the same recipe map.
+    pub fn resume(&mut self, graph: &Graph) {
+        match &self.targets {
+            Some(targets) => {
+                let graph = graph.restrict(&graph.ancestors(targets));
+                self.search(&graph);
+            }
+            None => self.search(graph),
+        }
+    }
+
+    /// Updates a complete search after `recipes` were added to `graph` with `Graph::add_recipes`,
+    /// giving the same result as searching the updated graph from scratch.
+    ///
+    /// Only the items whose families may change are recomputed: they are cleared and the items
+    /// merged into them are relaxed again in the original order, so that `max_count` truncates
+    /// the same sets. Families already truncated by the memory budget are not restored. Fails if
+    /// the search was cancelled before completing.
+    pub fn add_recipes(&mut self, recipes: &[Recipe], graph: &Graph) -> anyhow::Result<()> {
+        if !self.is_complete() {
+            bail!("cannot update an unfinished search");
+        }
+        let n = graph.num_vertices();
+        if self.sets.len() < n {
+            self.sets.resize_with(n, UniformFamily::default);
+            self.in_que.resize(n, usize::MAX);
+            self.truncated.resize(n, false);
+        }
+        if let Some(targets) = self.targets.take() {
+            // ancestors which were not searched may have become relevant
+            let source = take(&mut self.source);
+            *self = Self {
+                observer: self.observer.take(),
+                cancel: self.cancel.take(),
+                checkpoint: self.checkpoint.take(),
+                num_threads: self.num_threads,
+                budget: self.budget,
+                ..Self::with_item_set(n, self.max_count, self.max_card)
+            };
+            self.search_to_targets(&source, &targets, graph);
+            return Ok(());
+        }
+
+        let affected = self.affected_by(recipes, graph);
+        for u in (0..n as u32).filter(|&u| affected[u as usize]) {
+            let old = usage(&self.sets[u as usize]);
+            self.sets[u as usize].clear();
+            self.replace_usage(old, (0, 0));
+            self.truncated[u as usize] = false;
+        }
+
+        // every item merged into an affected item in the full search
+        for u1 in 0..n as u32 {
+            if !affected[u1 as usize]
+                && self.reached(u1)
+                && graph.arcs_from(u1).any(|(_, u3)| affected[u3 as usize])
+            {
+                self.push(u1, self.min_card(u1));
+            }
+        }
         while let Some((Reverse(c), u)) = self.queue.pop() {
             if c == replace(&mut self.in_que[u as usize], usize::MAX) {
-                self.relax_from(u, graph);
+                self.relax_from(u, graph, Some(&affected));
+            }
+        }
+        Ok(())
+    }
+
+    /// Items whose families may change by adding `recipes`, which are the results of the
+    /// recipes with reachable inputs and the items crafted from them.
+    fn affected_by(&self, recipes: &[Recipe], graph: &Graph) -> Vec<bool> {
+        let mut affected = vec![false; graph.num_vertices()];
+        let is_source = |u: u32| self.min_card(u) == 0;
+        let mut stack = Vec::new();
+        let mut updated = true;
+        while updated {
+            updated = false;
+            for recipe in recipes {
+                let [u1, u2] = recipe.pair;
+                let u3 = recipe.result;
+                if affected[u3 as usize] || is_source(u3) {
+                    continue;
+                }
+                if [u1, u2]
+                    .iter()
+                    .all(|&u| self.reached(u) || affected[u as usize])
+                {
+                    affected[u3 as usize] = true;
+                    stack.push(u3);
+                    updated = true;
+                }
+            }
+            while let Some(u1) = stack.pop() {
+                for (u2, u3) in graph.arcs_from(u1) {
+                    if !affected[u3 as usize]
+                        && !is_source(u3)
+                        && (self.reached(u2) || affected[u2 as usize])
+                    {
+                        affected[u3 as usize] = true;
+                        stack.push(u3);
+                    }
+                }
+            }
+        }
+        affected
+    }
+
+    pub fn save_checkpoint(&self, path: &Path, fingerprint: u64) -> anyhow::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, bincode::serialize(&(fingerprint, self))?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Loads a search saved by `save_checkpoint`, failing if it was made against a recipe map
+    /// with a different fingerprint.
+    pub fn load_checkpoint(path: &Path, fingerprint: u64) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)?;
+        let (saved, search): (u64, Self) =
+            bincode::deserialize(&data).context("invalid checkpoint file")?;
+        if saved != fingerprint {
+            bail!(
+                "checkpoint was made against a different recipe map ({:016x} vs {:016x})",
+                saved,
+                fingerprint
+            );
+        }
+        Ok(search)
+    }
+
+    fn search(&mut self, graph: &Graph) {
+        let instant = Instant::now();
+        if self.num_threads > 1 {
+            self.search_parallel(graph, instant);
+        } else {
+            self.search_sequential(graph, instant);
+        }
+        if !self.is_complete() {
+            self.write_checkpoint();
+        }
+        self.report(instant);
+    }
+
+    fn search_sequential(&mut self, graph: &Graph, instant: Instant) {
+        while !self.is_cancelled() {
+            self.checkpoint_if_due();
+            let Some((Reverse(c), u)) = self.queue.pop() else {
+                break;
+            };
+            if c == replace(&mut self.in_que[u as usize], usize::MAX) {
+                self.settle(c, 1, instant);
+                self.relax_from(u, graph, None);
             }
         }
     }
 
-    fn relax_from(&mut self, u1: u32, graph: &Graph) {
+    fn relax_from(&mut self, u1: u32, graph: &Graph, only: Option<&[bool]>) {
         let c1 = self.min_card(u1);
         for (u2, u3) in graph.arcs_from(u1) {
             if c1 < self.min_card(u2) || self.min_card(u3) <= c1 {
                 continue;
             }
+            if only.is_some_and(|only| !only[u3 as usize]) {
+                continue;
+            }
+            if self.is_truncated(u1) || self.is_truncated(u2) {
+                self.truncated[u3 as usize] = true;
+            }
             let mut sets3 = take(&mut self.sets[u3 as usize]);
+            let old = usage(&sets3);
             if sets3.add_merge(self.sets(u1), self.sets(u2), u3, self.max_count) {
                 self.push(u3, sets3.card());
             }
+            self.replace_usage(old, usage(&sets3));
             self.sets[u3 as usize] = sets3;
+            self.enforce_budget(self.card + 1);
+        }
+    }
+
+    // Relaxing an item of card c only changes items of card greater than c, so the items of
+    // the same card are relaxed concurrently and the merges are applied in the sequential order.
+    fn search_parallel(&mut self, graph: &Graph, instant: Instant) {
+        let mut bucket = Vec::new();
+        while let Some(&(Reverse(c), _)) = self.queue.peek() {
+            if self.is_cancelled() {
+                break;
+            }
+            self.checkpoint_if_due();
+            bucket.clear();
+            while let Some(&(Reverse(c2), u)) = self.queue.peek() {
+                if c2 != c {
+                    break;
+                }
+                self.queue.pop();
+                if c == replace(&mut self.in_que[u as usize], usize::MAX) {
+                    bucket.push(u);
+                }
+            }
+            self.settle(c, bucket.len(), instant);
+            if bucket.len() == 1 {
+                self.relax_from(bucket[0], graph, None);
+                continue;
+            }
+
+            // merges are applied in the order of the sequential search, checking the budget after
+            // each, so both truncate at the same point
+            for (u1, u2, u3, candidates) in self.merge_candidates_parallel(&bucket, graph) {
+                if self.is_truncated(u1) || self.is_truncated(u2) {
+                    self.truncated[u3 as usize] = true;
+                }
+                let sets3 = &mut self.sets[u3 as usize];
+                let old = usage(sets3);
+                let updated = sets3.add_candidates(candidates, self.max_count);
+                let new = usage(sets3);
+                let c3 = sets3.card();
+                self.replace_usage(old, new);
+                if updated {
+                    self.push(u3, c3);
+                }
+                self.enforce_budget(self.card + 1);
+            }
+        }
+    }
+
+    fn merge_candidates_parallel(
+        &self,
+        bucket: &[u32],
+        graph: &Graph,
+    ) -> Vec<(u32, u32, u32, Candidates<S>)> {
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.num_threads.min(bucket.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(&u1) = bucket.get(i) else {
+                                break;
+                            };
+                            results.push((i, self.merge_candidates_from(u1, graph)));
+                        }
+                        results
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        results.sort_by_key(|&(i, _)| i);
+        results.into_iter().flat_map(|(_, merges)| merges).collect()
+    }
+
+    /// Merges of `u1` as `(u1, u2, u3, candidates)`. The candidates use the `max_count` at the
+    /// start of the bucket, which the budget may only lower while applying them.
+    fn merge_candidates_from(&self, u1: u32, graph: &Graph) -> Vec<(u32, u32, u32, Candidates<S>)> {
+        let c1 = self.min_card(u1);
+        let mut merges = Vec::new();
+        for (u2, u3) in graph.arcs_from(u1) {
+            if c1 < self.min_card(u2) || self.min_card(u3) <= c1 {
+                continue;
+            }
+            let mut candidates =
+                UniformFamily::merge_candidates(self.sets(u1), self.sets(u2), u3, self.max_count);
+            if self.min_card(u3) < candidates.card() {
+                candidates = Candidates::default();
+            }
+            merges.push((u1, u2, u3, candidates));
+        }
+        merges
+    }
+
+    fn checkpoint_if_due(&mut self) {
+        if let Some(checkpoint) = &self.checkpoint {
+            if checkpoint.interval <= checkpoint.last.elapsed() {
+                self.write_checkpoint();
+            }
+        }
+    }
+
+    fn write_checkpoint(&mut self) {
+        if let Some(checkpoint) = &self.checkpoint {
+            if let Err(e) = self.save_checkpoint(&checkpoint.path, checkpoint.fingerprint) {
+                eprintln!(
+                    "Failed to write checkpoint {}: {:#}",
+                    checkpoint.path.display(),
+                    e
+                );
+            }
+        }
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.last = Instant::now();
+        }
+    }
+
+    #[inline]
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+    }
+
+    fn settle(&mut self, c: usize, num_items: usize, instant: Instant) {
+        let prev_settled = self.num_settled;
+        self.num_settled += num_items;
+        let next_card = c != replace(&mut self.card, c);
+        if next_card {
+            self.enforce_budget(0);
+        }
+        if next_card || prev_settled / REPORT_INTERVAL != self.num_settled / REPORT_INTERVAL {
+            self.report(instant);
+        }
+    }
+
+    // Called after every merge, in the same order by the sequential and the parallel search, and
+    // when moving to the next cardinality, so both truncate at the same points. Only the families
+    // of cardinality at least `min_card` are truncated: while relaxing the items of a cardinality,
+    // the settled families are still being merged, and only the unsettled ones grow.
+    fn enforce_budget(&mut self, min_card: usize) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        // with one set per family the budget cannot be met, and the unsettled families cannot
+        // grow beyond one, so only the move to the next cardinality has anything to truncate
+        if self.max_count <= 1 && min_card > 0 {
+            return;
+        }
+        while match budget {
+            MemoryBudget::Sets(max_sets) => max_sets < self.num_sets,
+            MemoryBudget::Bytes(max_bytes) => max_bytes < self.num_bytes,
+        } {
+            let max_len = self
+                .sets
+                .iter()
+                .filter(|sets| min_card <= sets.card())
+                .map(|sets| sets.len())
+                .max()
+                .unwrap_or(0);
+            if max_len <= 1 {
+                self.max_count = 1;
+                break;
+            }
+            self.max_count = self.max_count.min(max_len / 2);
+            for u in 0..self.sets.len() {
+                if self.sets[u].card() < min_card {
+                    continue;
+                }
+                let old = usage(&self.sets[u]);
+                if self.sets[u].truncate(self.max_count) {
+                    self.truncated[u] = true;
+                }
+                self.replace_usage(old, usage(&self.sets[u]));
+            }
+        }
+    }
+
+    fn replace_usage(
+        &mut self,
+        (old_sets, old_bytes): (usize, usize),
+        (sets, bytes): (usize, usize),
+    ) {
+        self.num_sets = self.num_sets + sets - old_sets;
+        self.num_bytes = self.num_bytes + bytes - old_bytes;
+    }
+
+    fn report(&mut self, instant: Instant) {
+        let progress = Progress {
+            card: self.card,
+            num_settled: self.num_settled,
+            queue_len: self.queue.len(),
+            num_sets: self.num_sets,
+            elapsed: instant.elapsed(),
+        };
+        if let Some(observer) = &mut self.observer {
+            observer(&progress);
         }
     }
 
@@ -78,50 +611,351 @@ impl Search {
     }
 }
 
+fn usage<S: ItemSet>(sets: &UniformFamily<S>) -> (usize, usize) {
+    (sets.len(), sets.num_bytes())
+}
+
 #[cfg(test)]
 mod test {
-    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 
-    use crate::{find_path, RecipeMap};
+    use crate::{bitset::BitSet, find_path, progress::CancelToken, random_recipe_map, RecipeMap};
 
     use super::*;
 
     #[test]
     fn search_with_real_rm() -> Result<(), Box<dyn std::error::Error>> {
-        let rm = RecipeMap::from_serialized(bincode::deserialize(&std::fs::read(
-            "recipe-map.bincode",
-        )?)?);
+        let rm = RecipeMap::from_bytes(&std::fs::read("recipe-map.bincode")?)?;
         let source = ["Water", "Fire", "Wind", "Earth"].map(|u| rm.id(u));
         let max_card = 300;
-        let card1 = {
+        let search1 = {
             let mut search = Search::new(rm.num_items(), usize::MAX, max_card);
             search.search_from_source(&source, &rm.make_graph());
             check_all_paths(&search, &source, &rm);
-            get_card_all(&search)
+            search
         };
+        let card1 = get_card_all(&search1);
 
         // should give a consistent result regardless of the order of the recipes
         let mut rng = StdRng::seed_from_u64(1);
         let mut graph = rm.make_graph();
         for i in 0..graph.num_vertices() {
-            graph.arcs[graph.start[i]..graph.start[i + 1]].shuffle(&mut rng);
+            graph.arcs[graph.start[i] as usize..graph.start[i + 1] as usize].shuffle(&mut rng);
         }
         let card2 = {
             let mut search = Search::new(rm.num_items(), usize::MAX, max_card);
-            search.search_from_source(&source, &rm.make_graph());
+            search.search_from_source(&source, &graph);
             get_card_all(&search)
         };
         for u in rm.items() {
             assert_eq!(card1[u as usize], card2[u as usize], "{:?}", rm.name(u));
         }
 
+        // the parallel search should give an identical result to the sequential one
+        let search3 = {
+            let mut search = Search::new(rm.num_items(), usize::MAX, max_card);
+            search.set_num_threads(8);
+            search.search_from_source(&source, &rm.make_graph());
+            search
+        };
+        check_same_sets(&search1, &search3, &rm);
+
         Ok(())
     }
 
+    #[test]
+    fn parallel_search_matches_sequential_search() {
+        let mut rng = StdRng::seed_from_u64(6);
+        for max_count in [1, 3, usize::MAX] {
+            for _ in 0..20 {
+                let rm = random_recipe_map(&mut rng, 60, 1000);
+                let graph = rm.make_graph();
+                let source: Vec<u32> = rm.items().take(4).collect();
+
+                let mut search1 = Search::new(rm.num_items(), max_count, usize::MAX);
+                search1.search_from_source(&source, &graph);
+                let mut search2 = Search::new(rm.num_items(), max_count, usize::MAX);
+                search2.set_num_threads(4);
+                search2.search_from_source(&source, &graph);
+                check_same_sets(&search1, &search2, &rm);
+            }
+        }
+    }
+
+    #[test]
+    fn search_to_targets_matches_full_search() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let rm = random_recipe_map(&mut rng, 20, 80);
+            let graph = rm.make_graph();
+            let source: Vec<u32> = rm.items().take(3).collect();
+
+            let mut full = Search::new(rm.num_items(), 2, usize::MAX);
+            full.search_from_source(&source, &graph);
+            for target in rm.items() {
+                let mut directed = Search::new(rm.num_items(), 2, usize::MAX);
+                directed.search_to_targets(&source, &[target], &graph);
+                let ancestors = graph.ancestors(&[target]);
+                for u in rm.items().filter(|&u| ancestors[u as usize]) {
+                    assert_eq!(
+                        directed.sets(u).iter().collect::<Vec<_>>(),
+                        full.sets(u).iter().collect::<Vec<_>>(),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bitset_search_matches_vec_search() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..50 {
+            let rm = random_recipe_map(&mut rng, 100, 400);
+            let graph = rm.make_graph();
+            let source: Vec<u32> = rm.items().take(4).collect();
+
+            let mut search1 = Search::new(rm.num_items(), 3, usize::MAX);
+            search1.search_from_source(&source, &graph);
+            let mut search2 = Search::<BitSet>::with_item_set(rm.num_items(), 3, usize::MAX);
+            search2.search_from_source(&source, &graph);
+            for u in rm.items() {
+                assert_eq!(
+                    search1.sets(u).iter().cloned().collect::<Vec<_>>(),
+                    search2
+                        .sets(u)
+                        .iter()
+                        .map(|set| set.to_vec())
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_search_keeps_settled_results() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for num_threads in [1, 4] {
+            for _ in 0..20 {
+                let rm = random_recipe_map(&mut rng, 60, 1000);
+                let graph = rm.make_graph();
+                let source: Vec<u32> = rm.items().take(4).collect();
+
+                let mut full = Search::new(rm.num_items(), 3, usize::MAX);
+                full.search_from_source(&source, &graph);
+
+                let cancel = CancelToken::new();
+                let mut search = Search::new(rm.num_items(), 3, usize::MAX);
+                search.set_num_threads(num_threads);
+                search.set_cancel_token(cancel.clone());
+                search.set_observer(move |progress| {
+                    if progress.card >= 3 {
+                        cancel.cancel();
+                    }
+                });
+                search.search_from_source(&source, &graph);
+
+                let max_card = rm
+                    .items()
+                    .map(|u| full.min_card(u))
+                    .filter(|&c| c != usize::MAX)
+                    .max();
+                assert_eq!(search.is_complete(), max_card <= Some(3));
+                for u in rm.items() {
+                    if full.min_card(u) <= 3 {
+                        assert!(search.is_settled(u));
+                    }
+                    if search.is_settled(u) {
+                        assert_eq!(
+                            search.sets(u).iter().collect::<Vec<_>>(),
+                            full.sets(u).iter().collect::<Vec<_>>(),
+                        );
+                    }
+                }
+                let num_sets: usize = rm.items().map(|u| search.sets(u).len()).sum();
+                assert_eq!(search.num_sets(), num_sets);
+            }
+        }
+    }
+
+    #[test]
+    fn memory_budget_truncates_deterministically() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut num_truncated_searches = 0;
+        for _ in 0..20 {
+            let rm = random_recipe_map(&mut rng, 60, 1000);
+            let graph = rm.make_graph();
+            let source: Vec<u32> = rm.items().take(4).collect();
+
+            let mut full = Search::new(rm.num_items(), usize::MAX, usize::MAX);
+            full.search_from_source(&source, &graph);
+            let budget = full.num_sets() / 4;
+
+            let searches = [1, 4].map(|num_threads| {
+                let mut search = Search::new(rm.num_items(), usize::MAX, usize::MAX);
+                search.set_num_threads(num_threads);
+                search.set_memory_budget(MemoryBudget::Sets(budget));
+                search.search_from_source(&source, &graph);
+                search
+            });
+            let [search, parallel] = &searches;
+            check_same_sets(search, parallel, &rm);
+            assert_eq!(
+                search.truncated_items().collect::<Vec<_>>(),
+                parallel.truncated_items().collect::<Vec<_>>()
+            );
+
+            if search.max_count() < usize::MAX {
+                assert!(search.truncated_items().count() > 0);
+                num_truncated_searches += 1;
+            }
+            for u in rm.items() {
+                if !search.is_truncated(u) {
+                    assert_eq!(
+                        search.sets(u).iter().collect::<Vec<_>>(),
+                        full.sets(u).iter().collect::<Vec<_>>(),
+                    );
+                }
+            }
+            let num_sets: usize = rm.items().map(|u| search.sets(u).len()).sum();
+            let num_bytes: usize = rm.items().map(|u| search.sets(u).num_bytes()).sum();
+            assert_eq!(search.num_sets(), num_sets);
+            assert_eq!(search.num_bytes(), num_bytes);
+        }
+        assert!(num_truncated_searches > 0);
+    }
+
+    #[test]
+    fn memory_budget_holds_within_a_cardinality() {
+        let mut rm = RecipeMap::new();
+        let source = ["S0", "S1", "S2", "S3"];
+        let mut items = Vec::new();
+        for (i, first) in source.iter().enumerate() {
+            for second in &source[i..] {
+                let result = format!("{}{}", first, second);
+                rm.insert(first, second, &result);
+                items.push(result);
+            }
+        }
+        for (i, first) in items.iter().enumerate() {
+            for second in &items[i..] {
+                rm.insert(first, second, "X");
+            }
+        }
+        let graph = rm.make_graph();
+        let source = source.map(|u| rm.id(u));
+        let x = rm.id("X");
+
+        // X has card 2, above max_card, so the search ends while relaxing the items of card 1
+        for num_threads in [1, 4] {
+            let mut search = Search::new(rm.num_items(), usize::MAX, 1);
+            search.set_num_threads(num_threads);
+            search.set_memory_budget(MemoryBudget::Sets(20));
+            search.search_from_source(&source, &graph);
+            assert!(search.num_sets() <= 20, "{}", search.num_sets());
+            assert!(search.is_truncated(x));
+            assert_eq!(search.sets(x).card(), 2);
+        }
+
+        // a budget below one set per item leaves one set per family
+        for num_threads in [1, 4] {
+            let mut search = Search::new(rm.num_items(), usize::MAX, usize::MAX);
+            search.set_num_threads(num_threads);
+            search.set_memory_budget(MemoryBudget::Sets(1));
+            search.search_from_source(&source, &graph);
+            assert!(search.is_complete());
+            assert_eq!(search.max_count(), 1);
+            assert!(rm.items().all(|u| search.sets(u).len() <= 1));
+            assert_eq!(search.sets(x).card(), 2);
+        }
+    }
+
+    #[test]
+    fn resume_from_checkpoint() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.bin", std::process::id()));
+        let mut rng = StdRng::seed_from_u64(9);
+        for num_threads in [1, 4] {
+            let rm = random_recipe_map(&mut rng, 60, 1000);
+            let graph = rm.make_graph();
+            let source: Vec<u32> = rm.items().take(4).collect();
+            let targets: Vec<u32> = rm.items().rev().take(2).collect();
+
+            let mut full = Search::new(rm.num_items(), 3, usize::MAX);
+            full.search_to_targets(&source, &targets, &graph);
+
+            let cancel = CancelToken::new();
+            let mut search = Search::new(rm.num_items(), 3, usize::MAX);
+            search.set_num_threads(num_threads);
+            search.set_checkpoint(&path, Duration::ZERO, rm.fingerprint());
+            search.set_cancel_token(cancel.clone());
+            search.set_observer(move |progress| {
+                if progress.card >= 2 {
+                    cancel.cancel();
+                }
+            });
+            search.search_to_targets(&source, &targets, &graph);
+            assert!(!search.is_complete());
+            assert!(search.add_recipes(&[], &graph).is_err());
+
+            let mut other = random_recipe_map(&mut rng, 60, 1000);
+            other.insert("x", "y", "z");
+            assert!(Search::<Vec<u32>>::load_checkpoint(&path, other.fingerprint()).is_err());
+
+            let mut resumed = Search::<Vec<u32>>::load_checkpoint(&path, rm.fingerprint())?;
+            assert_eq!(resumed.source(), &source);
+            resumed.resume(&graph);
+            assert!(resumed.is_complete());
+            check_same_sets(&full, &resumed, &rm);
+        }
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn add_recipes_matches_full_search() {
+        let mut rng = StdRng::seed_from_u64(9);
+        for max_count in [3, usize::MAX] {
+            for _ in 0..20 {
+                let names: Vec<String> = (0..50).map(|i| format!("{:02}", i)).collect();
+                let random_recipe = |rng: &mut StdRng| {
+                    [(); 3].map(|_| names[rng.gen_range(0..names.len())].as_str())
+                };
+                let mut rm = RecipeMap::new();
+                for [first, second, result] in (0..300).map(|_| random_recipe(&mut rng)) {
+                    rm.insert(first, second, result);
+                }
+                let mut graph = rm.make_graph();
+                let source: Vec<u32> = rm.items().take(4).collect();
+                let mut search = Search::new(rm.num_items(), max_count, usize::MAX);
+                search.search_from_source(&source, &graph);
+
+                for _ in 0..5 {
+                    let mut recipes = Vec::new();
+                    for [first, second, result] in (0..40).map(|_| random_recipe(&mut rng)) {
+                        if rm.insert(first, second, result) {
+                            recipes.push(Recipe::new(rm.id(first), rm.id(second), rm.id(result)));
+                        }
+                    }
+                    let added = graph.add_recipes(rm.num_items(), &recipes);
+                    assert_eq!(added.len(), recipes.len());
+                    search.add_recipes(&added, &graph).unwrap();
+
+                    let full_graph = rm.make_graph();
+                    for u in rm.items() {
+                        assert!(graph.arcs_from(u).eq(full_graph.arcs_from(u)));
+                        assert!(graph.arcs_to(u).eq(full_graph.arcs_to(u)));
+                    }
+                    let mut full = Search::new(rm.num_items(), max_count, usize::MAX);
+                    full.search_from_source(&source, &full_graph);
+                    check_same_sets(&search, &full, &rm);
+                }
+            }
+        }
+    }
+
     fn check_all_paths(search: &Search, source: &[u32], rm: &RecipeMap) {
         for u in rm.items() {
             for set in search.sets(u).iter() {
-                let path = find_path(&source, set, &rm).unwrap();
+                let path = find_path(source, set, rm).unwrap();
                 let mut path_set: Vec<_> = path.iter().map(|r| r.result).collect();
                 path_set.sort();
                 assert_eq!(&path_set, set);
@@ -129,6 +963,17 @@ mod test {
         }
     }
 
+    fn check_same_sets(search1: &Search, search2: &Search, rm: &RecipeMap) {
+        for u in rm.items() {
+            assert_eq!(
+                search1.sets(u).iter().collect::<Vec<_>>(),
+                search2.sets(u).iter().collect::<Vec<_>>(),
+                "{:?}",
+                rm.name(u)
+            );
+        }
+    }
+
     fn get_card_all(search: &Search) -> Vec<usize> {
         search.sets.iter().map(|sets| sets.card()).collect()
     }
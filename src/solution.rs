@@ -0,0 +1,177 @@
+//! Serializable solutions, for tools consuming the results of the solver.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{find_path, search::Search, uniform_family::ItemSet, Recipe, RecipeMap};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: u32,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+}
+
+impl Item {
+    pub fn new(u: u32, rm: &RecipeMap) -> Self {
+        Self {
+            id: u,
+            name: rm.name(u).to_string(),
+            emoji: rm.item_info(u).and_then(|info| info.emoji.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub first: Item,
+    pub second: Item,
+    pub result: Item,
+}
+
+impl Step {
+    pub fn new(recipe: &Recipe, rm: &RecipeMap) -> Self {
+        let [first, second] = recipe.pair.map(|u| Item::new(u, rm));
+        Self {
+            first,
+            second,
+            result: Item::new(recipe.result, rm),
+        }
+    }
+}
+
+/// Paths of minimum cardinality from the source items to a target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Solution {
+    pub target: Item,
+    pub sources: Vec<Item>,
+    /// Number of steps of each path, or `None` if the target was not reached.
+    pub card: Option<usize>,
+    /// Number of sets of the target found, one path each.
+    pub num_alternatives: usize,
+    /// Steps of each path, ordered so that the inputs of a step are sources or results of
+    /// earlier steps.
+    pub paths: Vec<Vec<Step>>,
+    /// The search was cancelled before the target was settled, so cheaper paths may exist.
+    pub partial: bool,
+    /// Sets of the target were dropped by `max_count` or a limit on the number of paths, so other,
+    /// or even cheaper, paths may exist.
+    pub truncated: bool,
+    /// The cardinality was proven minimum by the exact search.
+    pub optimal: bool,
+}
+
+impl Solution {
+    /// Solution with a path for each set of the target found by the search.
+    pub fn from_search<S: ItemSet>(search: &Search<S>, target: u32, rm: &RecipeMap) -> Self {
+        let source = search.source();
+        let paths = search
+            .sets(target)
+            .iter()
+            .filter_map(|set| {
+                let mut set = set.to_vec();
+                set.sort_by_key(|&u| search.min_card(u));
+                find_path(source, &set, rm)
+            })
+            .collect();
+        Self {
+            card: Some(search.min_card(target)).filter(|_| search.reached(target)),
+            num_alternatives: search.sets(target).len(),
+            partial: !search.is_settled(target),
+            truncated: search.is_truncated(target),
+            ..Self::from_paths(source, target, paths, rm)
+        }
+    }
+
+    /// Solution with the given paths, all of the same cardinality.
+    pub fn from_paths(
+        source: &[u32],
+        target: u32,
+        paths: Vec<Vec<Recipe>>,
+        rm: &RecipeMap,
+    ) -> Self {
+        Self {
+            target: Item::new(target, rm),
+            sources: source.iter().map(|&u| Item::new(u, rm)).collect(),
+            card: paths.first().map(|path| path.len()),
+            num_alternatives: paths.len(),
+            paths: paths
+                .iter()
+                .map(|path| path.iter().map(|r| Step::new(r, rm)).collect())
+                .collect(),
+            partial: false,
+            truncated: false,
+            optimal: false,
+        }
+    }
+
+    /// Marks the solution optimal given a path of minimum cardinality found by the exact search.
+    /// If the paths of the solution are longer, they are replaced by it, and the solution is
+    /// marked truncated since other paths of its cardinality may exist.
+    pub fn set_optimal_path(&mut self, source: &[u32], path: Vec<Recipe>, rm: &RecipeMap) {
+        if self.card != Some(path.len()) {
+            *self = Self {
+                truncated: true,
+                ..Self::from_paths(source, self.target.id, vec![path], rm)
+            };
+        }
+        self.optimal = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::example_recipe_map;
+
+    use super::*;
+
+    #[test]
+    fn solution_round_trip() {
+        let rm = example_recipe_map();
+        let graph = rm.make_graph();
+        let source = ["Water", "Fire", "Earth"].map(|u| rm.id(u));
+        let mut search = Search::new(rm.num_items(), usize::MAX, 10);
+        search.search_from_source(&source, &graph);
+
+        let geyser = rm.id("Geyser");
+        let solution = Solution::from_search(&search, geyser, &rm);
+        assert_eq!(solution.target.name, "Geyser");
+        assert_eq!(solution.sources.len(), 3);
+        assert_eq!(solution.card, Some(2));
+        assert_eq!(solution.num_alternatives, 2);
+        assert_eq!(solution.paths.len(), 2);
+        for path in &solution.paths {
+            assert_eq!(path.len(), 2);
+            assert_eq!(path[1].result.id, geyser);
+            assert!([path[1].first.id, path[1].second.id].contains(&path[0].result.id));
+        }
+        assert!(!solution.partial && !solution.truncated && !solution.optimal);
+
+        let json = serde_json::to_string(&solution).unwrap();
+        assert!(json.contains(r#""target":{"id":"#));
+        assert_eq!(serde_json::from_str::<Solution>(&json).unwrap(), solution);
+
+        let eclipse = Solution::from_search(&search, rm.id("Eclipse"), &rm);
+        assert_eq!(eclipse.card, None);
+        assert!(eclipse.paths.is_empty());
+
+        let [water, fire, earth, steam, engine] =
+            ["Water", "Fire", "Earth", "Steam", "Engine"].map(|u| rm.id(u));
+        let shortest = vec![
+            Recipe::new(water, fire, steam),
+            Recipe::new(steam, earth, geyser),
+        ];
+        let mut optimal = solution.clone();
+        optimal.set_optimal_path(&source, shortest.clone(), &rm);
+        assert_eq!(optimal.paths, solution.paths);
+        assert!(optimal.optimal && !optimal.truncated);
+
+        let mut longer = shortest.clone();
+        longer.insert(1, Recipe::new(steam, fire, engine));
+        let mut replaced = Solution::from_paths(&source, geyser, vec![longer], &rm);
+        replaced.set_optimal_path(&source, shortest.clone(), &rm);
+        assert_eq!(replaced.card, Some(2));
+        assert_eq!(replaced.num_alternatives, 1);
+        assert!(replaced.optimal && replaced.truncated);
+    }
+}
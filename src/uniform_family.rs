@@ -1,16 +1,91 @@
-use std::{collections::BTreeSet, fmt::Debug};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+};
 
-#[derive(Debug, Clone)]
-pub struct UniformFamily {
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Set of item ids stored in a `UniformFamily`.
+///
+/// `Ord` must agree with the lexicographic order of the sorted ids so that every
+/// representation iterates the families, and thus truncates them by `max_count`, identically.
+pub trait ItemSet:
+    Clone + Ord + Debug + Default + Send + Sync + Serialize + DeserializeOwned
+{
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, u: u32) -> bool;
+
+    /// Cardinality of the union of `self` and `other`.
+    fn union_len(&self, other: &Self) -> usize;
+
+    /// Union of `self` and `other` with `u` added.
+    fn union_with(&self, other: &Self, u: u32) -> Self;
+
+    /// Sorted item ids.
+    fn to_vec(&self) -> Vec<u32>;
+
+    /// Bytes allocated on the heap by the set.
+    fn heap_size(&self) -> usize;
+}
+
+impl ItemSet for Vec<u32> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn contains(&self, u: u32) -> bool {
+        self.as_slice().contains(&u)
+    }
+
+    fn union_len(&self, other: &Self) -> usize {
+        merge_card(self, other)
+    }
+
+    fn union_with(&self, other: &Self, u: u32) -> Self {
+        let mut out = Vec::with_capacity(self.len() + other.len() + 1);
+        merge(self, other, &mut out);
+        if let Err(i) = out.binary_search(&u) {
+            out.insert(i, u);
+        }
+        out
+    }
+
+    fn to_vec(&self) -> Vec<u32> {
+        self.clone()
+    }
+
+    fn heap_size(&self) -> usize {
+        self.capacity() * std::mem::size_of::<u32>()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "S: ItemSet")]
+pub struct UniformFamily<S = Vec<u32>> {
     card: usize,
-    sets: BTreeSet<Vec<u32>>,
+    sets: BTreeSet<S>,
+    num_bytes: usize,
 }
 
 impl UniformFamily {
     pub fn new() -> Self {
+        Self::with_item_set()
+    }
+}
+
+impl<S: ItemSet> UniformFamily<S> {
+    pub fn with_item_set() -> Self {
         Self {
             card: usize::MAX,
             sets: BTreeSet::new(),
+            num_bytes: 0,
         }
     }
 
@@ -20,13 +95,43 @@ impl UniformFamily {
 
     pub fn clear(&mut self) {
         self.card = usize::MAX;
-        self.sets.clear();
+        self.clear_sets();
     }
 
     pub fn set_single_empty(&mut self) {
         self.card = 0;
+        self.clear_sets();
+        self.insert(S::default());
+    }
+
+    /// Approximate bytes used by the sets, excluding the tree nodes.
+    #[inline]
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    /// Keeps only the `max_len` lexicographically smallest sets. Returns whether any set was
+    /// removed.
+    pub fn truncate(&mut self, max_len: usize) -> bool {
+        let Some(first_removed) = self.sets.iter().nth(max_len).cloned() else {
+            return false;
+        };
+        for set in self.sets.split_off(&first_removed) {
+            self.num_bytes -= set_bytes(&set);
+        }
+        true
+    }
+
+    fn insert(&mut self, set: S) {
+        let num_bytes = set_bytes(&set);
+        if self.sets.insert(set) {
+            self.num_bytes += num_bytes;
+        }
+    }
+
+    fn clear_sets(&mut self) {
         self.sets.clear();
-        self.sets.insert(vec![]);
+        self.num_bytes = 0;
     }
 
     #[inline]
@@ -40,8 +145,8 @@ impl UniformFamily {
     }
 
     #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = &[u32]> {
-        self.sets.iter().map(|set| set.as_slice())
+    pub fn iter(&self) -> impl Iterator<Item = &S> {
+        self.sets.iter()
     }
 
     pub fn add_merge(&mut self, sets1: &Self, sets2: &Self, u3: u32, max_len: usize) -> bool {
@@ -52,43 +157,115 @@ impl UniformFamily {
         updated
     }
 
-    pub fn add_merge_1(&mut self, set1: &[u32], sets2: &Self, u3: u32, max_len: usize) -> bool {
+    pub fn add_merge_1(&mut self, set1: &S, sets2: &Self, u3: u32, max_len: usize) -> bool {
         let mut updated = false;
-        let u3_in_set1 = set1.contains(&u3);
+        let u3_in_set1 = set1.contains(u3);
         for set2 in sets2.iter() {
-            let c3 = merge_card(set1, set2) + !(u3_in_set1 || set2.contains(&u3)) as usize;
+            let c3 = set1.union_len(set2) + !(u3_in_set1 || set2.contains(u3)) as usize;
             if self.card < c3 {
                 continue;
             }
             if c3 < self.card {
                 self.card = c3;
-                self.sets.clear();
+                self.clear_sets();
                 updated = true;
             }
             if max_len <= self.len() {
                 continue;
             }
 
-            let mut set3 = Vec::with_capacity(c3);
-            merge(set1, set2, &mut set3);
-            if let Err(i) = set3.binary_search(&u3) {
-                set3.insert(i, u3);
-            }
+            let set3 = set1.union_with(set2, u3);
 
             assert!(set3.len() == c3);
 
-            self.sets.insert(set3);
+            self.insert(set3);
+        }
+        updated
+    }
+
+    /// Computes what `add_merge` would add to an empty family without modifying `self`, so
+    /// that merges can be computed concurrently and applied later with `add_candidates`.
+    pub fn merge_candidates(sets1: &Self, sets2: &Self, u3: u32, max_len: usize) -> Candidates<S> {
+        let mut card = usize::MAX;
+        let mut order = BTreeMap::new();
+        for set1 in sets1.iter() {
+            let u3_in_set1 = set1.contains(u3);
+            for set2 in sets2.iter() {
+                let c3 = set1.union_len(set2) + !(u3_in_set1 || set2.contains(u3)) as usize;
+                if card < c3 {
+                    continue;
+                }
+                if c3 < card {
+                    card = c3;
+                    order.clear();
+                }
+                if max_len <= order.len() {
+                    continue;
+                }
+                let len = order.len();
+                order.entry(set1.union_with(set2, u3)).or_insert(len);
+            }
+        }
+        let mut sets: Vec<_> = order.into_iter().collect();
+        sets.sort_by_key(|&(_, i)| i);
+        Candidates {
+            card,
+            sets: sets.into_iter().map(|(set, _)| set).collect(),
+        }
+    }
+
+    /// Adds candidates from `merge_candidates` with the same result as the `add_merge` call.
+    pub fn add_candidates(&mut self, candidates: Candidates<S>, max_len: usize) -> bool {
+        let mut updated = false;
+        if candidates.card < self.card {
+            self.card = candidates.card;
+            self.clear_sets();
+            updated = true;
+        }
+        if candidates.card == self.card {
+            for set in candidates.sets {
+                if max_len <= self.len() {
+                    break;
+                }
+                self.insert(set);
+            }
         }
         updated
     }
 }
 
-impl Default for UniformFamily {
+/// Sets of equal cardinality in the order `add_merge` would insert them.
+#[derive(Debug, Clone)]
+pub struct Candidates<S> {
+    card: usize,
+    sets: Vec<S>,
+}
+
+impl<S> Default for Candidates<S> {
+    fn default() -> Self {
+        Self {
+            card: usize::MAX,
+            sets: Vec::new(),
+        }
+    }
+}
+
+impl<S> Candidates<S> {
+    pub fn card(&self) -> usize {
+        self.card
+    }
+}
+
+impl<S: ItemSet> Default for UniformFamily<S> {
     fn default() -> Self {
-        Self::new()
+        Self::with_item_set()
     }
 }
 
+fn set_bytes<S: ItemSet>(set: &S) -> usize {
+    std::mem::size_of::<S>() + set.heap_size()
+}
+
 fn merge_card(set1: &[u32], set2: &[u32]) -> usize {
     let mut iter2 = set2.iter().peekable();
     let mut card3 = set1.len() + set2.len();
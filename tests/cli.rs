@@ -0,0 +1,80 @@
+use std::{path::PathBuf, process::Command};
+
+use infinite_craft::RecipeMap;
+
+/// Writes a recipe map with two paths of card 2 to Geyser from Water, Fire and Earth.
+fn write_recipe_map(name: &str) -> PathBuf {
+    let mut rm = RecipeMap::new();
+    rm.insert("Water", "Fire", "Steam");
+    rm.insert("Water", "Earth", "Mud");
+    rm.insert("Steam", "Earth", "Geyser");
+    rm.insert("Mud", "Fire", "Geyser");
+    let path = std::env::temp_dir().join(format!("{}-{}.bincode", name, std::process::id()));
+    std::fs::write(&path, rm.to_bytes()).unwrap();
+    path
+}
+
+/// Runs `solve Geyser` in the format, returning stdout.
+fn solve(recipe_map: &PathBuf, format: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_infinite-craft"))
+        .arg("--recipe-map")
+        .arg(recipe_map)
+        .args([
+            "solve",
+            "Geyser",
+            "--source",
+            "Water,Fire,Earth",
+            "--format",
+            format,
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!output.stderr.is_empty());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn diagrams_alone_on_stdout() {
+    let recipe_map = write_recipe_map("diagrams");
+    for args in [&[][..], &["--all"], &["--exact"]] {
+        let dot = solve(&recipe_map, "dot", args);
+        let graphs: Vec<_> = dot.split_inclusive("}\n").collect();
+        assert!(!graphs.is_empty());
+        for graph in graphs {
+            assert!(graph.starts_with("digraph path {\n"), "{}", dot);
+            assert!(graph
+                .lines()
+                .skip(1)
+                .all(|line| line == "}" || line.starts_with("  ") && line.ends_with(';')));
+        }
+
+        let mermaid = solve(&recipe_map, "mermaid", args);
+        assert!(mermaid.starts_with("flowchart TD\n"), "{}", mermaid);
+        assert!(mermaid
+            .lines()
+            .all(|line| line == "flowchart TD" || line.starts_with("  ")));
+    }
+    std::fs::remove_file(recipe_map).unwrap();
+}
+
+#[test]
+fn json_alone_on_stdout() {
+    let recipe_map = write_recipe_map("json");
+    let json = solve(&recipe_map, "json", &["--exact"]);
+    let solutions: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let solutions = solutions.as_array().unwrap();
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(solutions[0]["card"], 2);
+    assert_eq!(solutions[0]["optimal"], true);
+
+    for (max_paths, num_alternatives, truncated) in [("2", 2, false), ("1", 1, true)] {
+        let jsonl = solve(&recipe_map, "jsonl", &["--all", "--max-paths", max_paths]);
+        let solution: serde_json::Value = serde_json::from_str(&jsonl).unwrap();
+        assert_eq!(solution["num_alternatives"], num_alternatives);
+        assert_eq!(solution["truncated"], truncated);
+        assert_eq!(solution["optimal"], true);
+    }
+    std::fs::remove_file(recipe_map).unwrap();
+}